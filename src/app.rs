@@ -3,58 +3,94 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 use std::cell::RefCell;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::{vec};
 
-use egui::{collapsing_header::CollapsingState, RichText};
-use egui::{ScrollArea, Layout, TextEdit, Stroke, Rounding, Modifiers, Key};
+use egui::RichText;
+use egui::{ScrollArea, Layout, TextEdit, Stroke, Rounding, Button};
 
-use egui_dock::{DockArea, DockState, Style, TabStyle};
+use egui_dock::{DockArea, DockState, Style};
 
 use uuid::Uuid;
 
-use crate::collection::Collection;
-use crate::tab_viewer::TabViewer;
+use crate::collection::{self, Collection, CollectionFile, Items, LegacyState};
+use crate::commands::{Command, CommandPalette, Keybindings, KeybindingsWindow, PaletteEntry};
+use crate::diff::DiffWindow;
+use crate::request::Request;
+use crate::sidebar::{Sidebar, SidebarAction};
+use crate::tab_viewer::{TabViewer, Draft, DRAFTS_KEY};
+use crate::workspace::Workspace;
 
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
-// TODO: Manually implement Deserialize so the Rc<RefCells<>> Work
+/// The tab viewer is not serialized and gets the collections and open requests again in [`PacketsApp::new`]
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)] // if we add new fields, give them default values when deserializing old state
 pub struct PacketsApp {
     #[serde(skip)]
-    selected_collection: Option<usize>,
-    #[serde(skip)]
-    selected_request: Option<usize>,
+    sidebar: Sidebar,
     
     #[serde(skip)]
     new_collection_name: String,
  
     collections: Rc<RefCell<Vec<Collection>>>,
+    /// The directory the collections were opened from or last saved to
+    workspace: Option<PathBuf>,
     
-    #[serde(skip)]
+    /// The layout and the open tabs
     dock_state: DockState<Uuid>,
     
     #[serde(skip)]
     tab_viewer: TabViewer,
+    
+    keybindings: Keybindings,
+    #[serde(skip)]
+    keybindings_window: KeybindingsWindow,
+    #[serde(skip)]
+    command_palette: CommandPalette,
+    #[serde(skip)]
+    diff_window: DiffWindow,
+    
+    /// The user wants to quit, but there are unsaved requests
+    #[serde(skip)]
+    confirm_quit: bool,
+    /// A workspace the user wants to open while there are unsaved requests
+    #[serde(skip)]
+    confirm_open: Option<PathBuf>,
+    /// The unsaved requests were saved or discarded, so quitting doesn't ask again
+    #[serde(skip)]
+    allowed_to_quit: bool,
+    /// The user chose to quit without saving, so the drafts are dropped as well
+    #[serde(skip)]
+    discard_drafts: bool,
+    /// Unsaved requests from the last time the app ran, waiting for the user to restore them
+    #[serde(skip)]
+    recovered_drafts: Vec<Draft>,
 }
 
 impl Default for PacketsApp {
     fn default() -> Self {
         let collections = Rc::new(RefCell::new(vec![]));
         Self {
-            selected_collection: None,
-            selected_request: None,
+            sidebar: Default::default(),
             new_collection_name: String::new(),
             collections: Rc::clone(&collections),
+            workspace: None,
             dock_state: DockState::new(vec![]),
             tab_viewer: TabViewer::new(Rc::clone(&collections)),
+            keybindings: Default::default(),
+            keybindings_window: Default::default(),
+            command_palette: Default::default(),
+            diff_window: Default::default(),
+            confirm_quit: false,
+            confirm_open: None,
+            allowed_to_quit: false,
+            discard_drafts: false,
+            recovered_drafts: vec![],
         }
     }
 }
 
-// TODO: Delete Collection
-// TODO: Free-Standing Requests
-// TODO: Global ctrl+s shortcut
 impl PacketsApp {
     /// Called once before the first frame.
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
@@ -64,34 +100,647 @@ impl PacketsApp {
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
         if let Some(storage) = cc.storage {
-            let mut app: PacketsApp = eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
+            let mut app: PacketsApp = match eframe::get_value(storage, eframe::APP_KEY) {
+                Some(app) => app,
+                // The state doesn't load anymore, keep at least the collections in it
+                None => {
+                    let legacy: LegacyState = eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
+                    let collections = legacy.into_collections();
+                    if !collections.is_empty() {
+                        log::warn!("The app state couldn't be loaded, only its collections were restored");
+                    }
+                    PacketsApp { collections: Rc::new(RefCell::new(collections)), ..Default::default() }
+                },
+            };
             app.tab_viewer = TabViewer::new(Rc::clone(&app.collections));
+            app.restore_tabs();
+            app.recovered_drafts = eframe::get_value(storage, DRAFTS_KEY).unwrap_or_default();
             return app;
         }
 
         Default::default()
     }
+
+    /// Drops the tabs of collections, folders and requests that are gone and opens the other requests again
+    fn restore_tabs(&mut self) {
+        let collections = self.collections.borrow();
+        self.dock_state.retain_tabs(|tab| collections.iter().any(|c| {
+            &c.uuid == tab || c.items.find_folder(tab).is_some() || c.items.find_request(tab).is_some()
+        }));
+        for (_, tab) in self.dock_state.iter_all_tabs() {
+            if let Some(request) = collections.iter().find_map(|c| c.items.find_request(tab)) {
+                self.tab_viewer.requests.insert(*tab, request.clone());
+            }
+        }
+    }
+
+    fn open_workspace(&mut self) {
+        let Some(path) = rfd::FileDialog::new().pick_folder() else {
+            return;
+        };
+        // The open tabs are closed with the old collections, so unsaved changes are asked about first
+        match self.tab_viewer.unsaved().is_empty() {
+            true => self.load_workspace(path),
+            false => self.confirm_open = Some(path),
+        }
+    }
+
+    fn load_workspace(&mut self, path: PathBuf) {
+        match Workspace::load(&path) {
+            Ok(workspace) => {
+                *self.collections.borrow_mut() = workspace.collections.into_iter().map(Collection::from_file).collect();
+                // The open tabs belong to the old collections
+                self.dock_state = DockState::new(vec![]);
+                self.tab_viewer.requests.clear();
+                self.sidebar.selected = None;
+                self.workspace = Some(path);
+            },
+            Err(e) => log::error!("Opening the workspace failed: {:#}", e),
+        }
+    }
+
+    /// Saves the collections to the current workspace directory, asking for one if there is none yet.
+    /// Returns false if they weren't saved
+    fn save_workspace(&mut self, choose_directory: bool) -> bool {
+        let path = match &self.workspace {
+            Some(path) if !choose_directory => path.clone(),
+            _ => match rfd::FileDialog::new().pick_folder() {
+                Some(path) => path,
+                None => return false,
+            },
+        };
+        let workspace = Workspace {
+            collections: self.collections.borrow().iter().map(Collection::to_file).collect(),
+        };
+        match workspace.save(&path) {
+            Ok(()) => {
+                self.workspace = Some(path);
+                true
+            },
+            Err(e) => {
+                log::error!("Saving the workspace failed: {:#}", e);
+                false
+            },
+        }
+    }
+
+    /// Opens a request that is not in any collection until it is saved
+    fn new_scratch_request(&mut self) {
+        let request = Request::scratch();
+        let uuid = request.uuid;
+        self.tab_viewer.requests.insert(uuid, request);
+        self.dock_state.push_to_focused_leaf(uuid);
+        self.sidebar.selected = None;
+    }
+
+    /// Asks where the scratch requests should be saved, one after the other
+    fn render_save_to_collection(&mut self, ctx: &egui::Context) {
+        // Tabs that were closed in the meantime don't need to be saved anymore
+        let requests = &self.tab_viewer.requests;
+        self.tab_viewer.save_to_collection.retain(|uuid| requests.contains_key(uuid));
+        let Some(&uuid) = self.tab_viewer.save_to_collection.front() else {
+            return;
+        };
+        let name = self.tab_viewer.requests[&uuid].name();
+        let mut open = true;
+        let mut target = None;
+        egui::Window::new("Save to Collection")
+            .open(&mut open)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                let collections = self.collections.borrow();
+                if collections.is_empty() {
+                    ui.weak("There are no collections yet. Create one in the side panel first.");
+                    return;
+                }
+                ui.label(format!("Choose a collection or folder for \"{}\"", name));
+                ui.add_space(5.);
+                ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+                    for collection in collections.iter() {
+                        if ui.selectable_label(false, &collection.name).clicked() {
+                            target = Some((collection.uuid, None));
+                        }
+                        render_folder_targets(ui, collection.uuid, &collection.items, &mut target);
+                    }
+                });
+            });
+
+        if let Some((collection, folder)) = target {
+            self.save_to_collection(uuid, collection, folder);
+        }
+        if !open || target.is_some() {
+            self.tab_viewer.save_to_collection.pop_front();
+        }
+    }
+
+    fn save_to_collection(&mut self, uuid: Uuid, collection: Uuid, folder: Option<Uuid>) {
+        let Some(request) = self.tab_viewer.requests.get_mut(&uuid) else {
+            return;
+        };
+        let mut collections = self.collections.borrow_mut();
+        let Some(collection) = collections.iter_mut().find(|c| c.uuid == collection) else {
+            return;
+        };
+        let Some(items) = collection.items.items_mut(folder.as_ref()) else {
+            return;
+        };
+        items.insert_request(request.clone(), None);
+        collection.relink();
+        // Now that it is in the collection the tab viewer saves it like any other request
+        request.wants_save = true;
+        self.sidebar.selected = Some(uuid);
+    }
+
+    fn open_tab(&mut self, uuid: Uuid) {
+        self.sidebar.selected = Some(uuid);
+        if let Some(tab_location) = self.dock_state.find_tab(&uuid) {
+            self.dock_state.set_active_tab(tab_location);
+            return;
+        }
+        if let Some(request) = self.collections.borrow().iter().find_map(|c| c.items.find_request(&uuid)) {
+            self.tab_viewer.requests.insert(uuid, request.clone());
+        }
+        self.dock_state.push_to_focused_leaf(uuid);
+    }
+    
+    fn close_tab(&mut self, uuid: &Uuid) {
+        if let Some(tab_location) = self.dock_state.find_tab(uuid) {
+            self.dock_state.remove_tab(tab_location);
+        }
+        self.tab_viewer.requests.remove(uuid);
+    }
+    
+    /// Closes the tab, but asks first if it has unsaved changes
+    fn try_close_tab(&mut self, uuid: Uuid) {
+        match self.tab_viewer.requests.get(&uuid) {
+            Some(request) if request.changed_since_save() => self.tab_viewer.confirm_close = Some(uuid),
+            _ => self.close_tab(&uuid),
+        }
+    }
+    
+    fn render_confirm_close(&mut self, ctx: &egui::Context) {
+        let Some(uuid) = self.tab_viewer.confirm_close else {
+            return;
+        };
+        let Some(request) = self.tab_viewer.requests.get(&uuid) else {
+            self.tab_viewer.confirm_close = None;
+            return;
+        };
+        let name = request.name();
+        let mut choice = None;
+        egui::Window::new("Unsaved Changes")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label(format!("\"{}\" has unsaved changes.", name));
+                ui.add_space(5.);
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        choice = Some(CloseChoice::Save);
+                    }
+                    if ui.button("Discard").clicked() {
+                        choice = Some(CloseChoice::Discard);
+                    }
+                    if ui.button("Cancel").clicked() || ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                        choice = Some(CloseChoice::Cancel);
+                    }
+                });
+            });
+        let Some(choice) = choice else {
+            return;
+        };
+        self.tab_viewer.confirm_close = None;
+        match choice {
+            CloseChoice::Save => {
+                // Scratch requests stay open while the user picks a collection for them
+                if self.tab_viewer.save(&uuid) {
+                    self.close_tab(&uuid);
+                }
+            },
+            CloseChoice::Discard => self.close_tab(&uuid),
+            CloseChoice::Cancel => {},
+        }
+    }
+    
+    fn render_recovered_drafts(&mut self, ctx: &egui::Context) {
+        if self.recovered_drafts.is_empty() {
+            return;
+        }
+        let mut choice = None;
+        egui::Window::new("Restore Unsaved Requests")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label("These requests had unsaved changes when Packets was closed:");
+                for draft in &self.recovered_drafts {
+                    ui.label(format!("• {}", draft.request_data.name));
+                }
+                ui.add_space(5.);
+                ui.horizontal(|ui| {
+                    if ui.button("Restore").clicked() {
+                        choice = Some(true);
+                    }
+                    if ui.button("Discard").clicked() {
+                        choice = Some(false);
+                    }
+                });
+            });
+        match choice {
+            Some(true) => {
+                for draft in std::mem::take(&mut self.recovered_drafts) {
+                    self.restore_draft(draft);
+                }
+            },
+            Some(false) => self.recovered_drafts.clear(),
+            None => {},
+        }
+    }
+    
+    /// Opens the request with the draft. Drafts of scratch requests or of deleted requests become scratch requests
+    fn restore_draft(&mut self, draft: Draft) {
+        let in_collection = self.collections.borrow().iter().any(|c| c.items.find_request(&draft.uuid).is_some());
+        if in_collection {
+            self.open_tab(draft.uuid);
+        } else {
+            let mut request = Request::scratch();
+            request.uuid = draft.uuid;
+            self.tab_viewer.requests.insert(draft.uuid, request);
+            self.dock_state.push_to_focused_leaf(draft.uuid);
+        }
+        if let Some(request) = self.tab_viewer.requests.get_mut(&draft.uuid) {
+            request.restore_draft(draft.request_data);
+        }
+    }
+    
+    fn render_confirm_quit(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        if !self.confirm_quit {
+            return;
+        }
+        let unsaved = self.tab_viewer.unsaved();
+        let Some(choice) = self.render_unsaved(ctx, &unsaved, "Quit Packets", "Quit Without Saving") else {
+            return;
+        };
+        self.confirm_quit = false;
+        let quit = match choice {
+            // A scratch request can't be saved without picking a collection, so quitting waits for that
+            CloseChoice::Save => unsaved.iter().filter(|uuid| !self.tab_viewer.save(uuid)).count() == 0,
+            CloseChoice::Discard => {
+                self.discard_drafts = true;
+                true
+            },
+            CloseChoice::Cancel => false,
+        };
+        if quit {
+            self.allowed_to_quit = true;
+            frame.close();
+        }
+    }
+
+    fn render_confirm_open(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.confirm_open.clone() else {
+            return;
+        };
+        let unsaved = self.tab_viewer.unsaved();
+        let Some(choice) = self.render_unsaved(ctx, &unsaved, "Open Workspace", "Open Without Saving") else {
+            return;
+        };
+        self.confirm_open = None;
+        let open = match choice {
+            // The saved requests would be gone with the old collections, so they are written to their workspace first
+            CloseChoice::Save => unsaved.iter().filter(|uuid| !self.tab_viewer.save(uuid)).count() == 0 && self.save_workspace(false),
+            CloseChoice::Discard => true,
+            CloseChoice::Cancel => false,
+        };
+        if open {
+            self.load_workspace(path);
+        }
+    }
+
+    /// Lists the unsaved requests and asks what to do with them
+    fn render_unsaved(&self, ctx: &egui::Context, unsaved: &[Uuid], title: &str, discard: &str) -> Option<CloseChoice> {
+        let mut names: Vec<String> = unsaved.iter()
+            .filter_map(|uuid| self.tab_viewer.requests.get(uuid))
+            .map(|r| r.name())
+            .collect();
+        names.sort();
+        let mut choice = None;
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label("These requests have unsaved changes:");
+                for name in &names {
+                    ui.label(format!("• {}", name));
+                }
+                ui.add_space(5.);
+                ui.horizontal(|ui| {
+                    if ui.button("Save All").clicked() {
+                        choice = Some(CloseChoice::Save);
+                    }
+                    if ui.button(discard).clicked() {
+                        choice = Some(CloseChoice::Discard);
+                    }
+                    if ui.button("Cancel").clicked() || ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                        choice = Some(CloseChoice::Cancel);
+                    }
+                });
+            });
+        choice
+    }
+    
+    fn apply_sidebar_action(&mut self, action: SidebarAction) {
+        match action {
+            SidebarAction::Open(uuid) => self.open_tab(uuid),
+            SidebarAction::NewRequest { collection, folder } => {
+                let created = self.collections.borrow_mut().iter_mut()
+                    .find(|c| c.uuid == collection)
+                    .and_then(|c| c.create_request(folder.as_ref()));
+                if let Some(uuid) = created {
+                    self.open_tab(uuid);
+                }
+            },
+            SidebarAction::NewFolder { collection, parent } => {
+                if let Some(collection) = self.collections.borrow_mut().iter_mut().find(|c| c.uuid == collection) {
+                    collection.create_folder(parent.as_ref());
+                }
+            },
+            SidebarAction::DuplicateRequest(uuid) => {
+                // TODO: Implement Duplicating Tabs
+                let duplicate = self.collections.borrow().iter()
+                    .find_map(|c| c.items.find_request(&uuid))
+                    .map(|r| r.duplicate());
+                if let Some(new_request) = duplicate {
+                    let uuid = new_request.uuid;
+                    self.tab_viewer.requests.insert(uuid, new_request);
+                    self.dock_state.push_to_focused_leaf(uuid);
+                }
+            },
+            SidebarAction::DeleteRequest(uuid) => {
+                self.close_tab(&uuid);
+                for collection in self.collections.borrow_mut().iter_mut() {
+                    collection.items.remove_request(&uuid);
+                }
+            },
+            SidebarAction::MoveRequest { request, collection, folder, before } => {
+                // Open tabs find the request again by its UUID
+                collection::move_request(&mut self.collections.borrow_mut(), &request, &collection, folder.as_ref(), before.as_ref());
+            },
+            SidebarAction::Rename { uuid, name } => {
+                for collection in self.collections.borrow_mut().iter_mut() {
+                    if collection.uuid == uuid {
+                        collection.name = name.clone();
+                    } else if let Some(folder) = collection.items.find_folder_mut(&uuid) {
+                        folder.name = name.clone();
+                    }
+                }
+            },
+            SidebarAction::DuplicateCollection(uuid) => {
+                let mut collections = self.collections.borrow_mut();
+                if let Some(i) = collections.iter().position(|c| c.uuid == uuid) {
+                    let duplicate = collections[i].duplicate();
+                    collections.insert(i + 1, duplicate);
+                }
+            },
+            SidebarAction::DeleteCollection(uuid) => {
+                let removed = {
+                    let mut collections = self.collections.borrow_mut();
+                    collections.iter().position(|c| c.uuid == uuid).map(|i| collections.remove(i))
+                };
+                if let Some(collection) = removed {
+                    self.close_tab(&collection.uuid);
+                    for uuid in collection.items.uuids() {
+                        self.close_tab(&uuid);
+                    }
+                }
+            },
+            SidebarAction::CollectionSettings(uuid) => {
+                if let Some(collection) = self.collections.borrow_mut().iter_mut().find(|c| c.uuid == uuid) {
+                    collection.show_settings();
+                }
+                self.open_tab(uuid);
+            },
+            SidebarAction::DeleteFolder(uuid) => {
+                let removed: Vec<_> = self.collections.borrow_mut().iter_mut()
+                    .filter_map(|c| c.items.remove_folder(&uuid))
+                    .collect();
+                for folder in removed {
+                    self.close_tab(&folder.uuid);
+                    for uuid in folder.items.uuids() {
+                        self.close_tab(&uuid);
+                    }
+                }
+            },
+        }
+    }
+    
+    /// The tab that has the focus
+    fn focused_tab(&mut self) -> Option<Uuid> {
+        self.dock_state.find_active_focused().map(|(_, tab)| *tab)
+    }
+    
+    /// The collection the focused tab belongs to
+    fn focused_collection(&mut self) -> Option<Uuid> {
+        let tab = self.focused_tab()?;
+        self.collections.borrow().iter()
+            .find(|c| c.uuid == tab || c.items.find_request(&tab).is_some() || c.items.find_folder(&tab).is_some())
+            .map(|c| c.uuid)
+    }
+    
+    fn run_command(&mut self, ctx: &egui::Context, command: Command) {
+        let focused = self.focused_tab();
+        let focused_request = focused.and_then(|tab| self.tab_viewer.requests.get_mut(&tab));
+        match command {
+            Command::OpenPalette => self.command_palette.open(ctx, ""),
+            Command::Send => {
+                if let Some(request) = focused_request {
+                    request.wants_send = true;
+                }
+            },
+            Command::Save => {
+                if let Some(request) = focused_request {
+                    request.wants_save = true;
+                }
+            },
+            Command::Undo => {
+                if let Some(request) = focused_request {
+                    request.undo();
+                }
+            },
+            Command::Redo => {
+                if let Some(request) = focused_request {
+                    request.redo();
+                }
+            },
+            Command::NewRequest => self.new_scratch_request(),
+            Command::DuplicateRequest => {
+                if let Some(uuid) = focused {
+                    self.apply_sidebar_action(SidebarAction::DuplicateRequest(uuid));
+                }
+            },
+            Command::CloseTab => {
+                if let Some(uuid) = focused {
+                    self.try_close_tab(uuid);
+                }
+            },
+            Command::SwitchEnvironment => self.command_palette.open(ctx, "Environment"),
+            Command::CompareResponses => match focused_request {
+                Some(request) => self.diff_window.compare_latest(request),
+                None => self.diff_window.open = true,
+            },
+            Command::Find => {
+                if let Some(request) = focused_request {
+                    request.find();
+                }
+            },
+            Command::Search => self.sidebar.focus_search(ctx),
+            Command::Import => self.import_collection(),
+            Command::OpenWorkspace => self.open_workspace(),
+            Command::SaveWorkspace => {
+                self.save_workspace(false);
+            },
+            Command::KeyboardShortcuts => self.keybindings_window.open = true,
+        }
+    }
+    
+    /// The commands and the environments of the focused collection, or of all collections if no tab is focused
+    fn palette_entries(&mut self) -> Vec<PaletteEntry> {
+        let mut entries: Vec<PaletteEntry> = Command::ALL.into_iter()
+            .filter(|command| command != &Command::OpenPalette)
+            .map(PaletteEntry::Command)
+            .collect();
+        let focused = self.focused_collection();
+        for collection in self.collections.borrow().iter().filter(|c| focused.map_or(true, |f| f == c.uuid)) {
+            let (environments, selected) = collection.environments();
+            if environments.is_empty() {
+                continue;
+            }
+            let label = |name: &str, active: bool| {
+                let active = if active { " ✔" } else { "" };
+                format!("Environment: {} ({}){}", name, collection.name, active)
+            };
+            entries.push(PaletteEntry::Environment {
+                collection: collection.uuid,
+                label: label("None", selected.is_none()),
+                name: None,
+            });
+            for name in environments {
+                entries.push(PaletteEntry::Environment {
+                    collection: collection.uuid,
+                    label: label(&name, selected.as_ref() == Some(&name)),
+                    name: Some(name),
+                });
+            }
+        }
+        entries
+    }
+    
+    fn import_collection(&mut self) {
+        let path = rfd::FileDialog::new()
+            .add_filter("Packets Collection", &["json"])
+            .pick_file();
+        if let Some(path) = path {
+            match CollectionFile::load(&path) {
+                Ok(file) => self.collections.borrow_mut().push(Collection::from_file(file)),
+                Err(e) => log::error!("Importing the collection failed: {:#}", e),
+            }
+        }
+    }
 }
 
 impl eframe::App for PacketsApp {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
         eframe::set_value(storage, eframe::APP_KEY, self);
+        // Drafts that weren't restored yet are kept for the next start
+        let mut drafts = match self.discard_drafts {
+            true => vec![],
+            false => self.tab_viewer.drafts(),
+        };
+        drafts.extend(self.recovered_drafts.iter().cloned());
+        eframe::set_value(storage, DRAFTS_KEY, &drafts);
+    }
+
+    /// Saves more often than the default, so a crash loses less of the drafts
+    fn auto_save_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(10)
+    }
+
+    /// Asks what to do with unsaved requests before quitting
+    fn on_close_event(&mut self) -> bool {
+        if self.allowed_to_quit || self.tab_viewer.unsaved().is_empty() {
+            return true;
+        }
+        self.confirm_quit = true;
+        false
     }
 
     /// Called each time the UI needs repainting, which may be many times per second.
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let mut delete_request = None;
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let mut sidebar_actions = vec![];
+        
+        if !self.keybindings_window.is_recording() {
+            // Only requests have their own undo. Everywhere else the text fields undo their edits
+            let request_focused = self.focused_tab().map_or(false, |tab| self.tab_viewer.requests.contains_key(&tab));
+            let enabled = |command| request_focused || !matches!(command, Command::Undo | Command::Redo);
+            for command in self.keybindings.pressed(ctx, enabled) {
+                self.run_command(ctx, command);
+            }
+        }
         
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.add_space(8.);
-            ui.heading(RichText::new("Packets").size(20.).strong());
+            ui.horizontal(|ui| {
+                ui.heading(RichText::new("Packets").size(20.).strong());
+                if ui.button("New Request").on_hover_text("A request outside of any collection").clicked() {
+                    self.new_scratch_request();
+                }
+                ui.menu_button("Workspace", |ui| {
+                    let open = Button::new("Open Workspace…").shortcut_text(self.keybindings.label(ctx, Command::OpenWorkspace));
+                    if ui.add(open).clicked() {
+                        self.open_workspace();
+                        ui.close_menu();
+                    }
+                    let save = Button::new("Save Workspace").shortcut_text(self.keybindings.label(ctx, Command::SaveWorkspace));
+                    if ui.add(save).clicked() {
+                        self.save_workspace(false);
+                        ui.close_menu();
+                    }
+                    if ui.button("Save Workspace As…").clicked() {
+                        self.save_workspace(true);
+                        ui.close_menu();
+                    }
+                });
+                ui.menu_button("Settings", |ui| {
+                    if ui.button("Keyboard Shortcuts…").clicked() {
+                        self.keybindings_window.open = true;
+                        ui.close_menu();
+                    }
+                });
+                let palette = format!("Commands ({})", self.keybindings.label(ctx, Command::OpenPalette));
+                if ui.button(palette).clicked() {
+                    self.command_palette.open(ctx, "");
+                }
+                if let Some(workspace) = &self.workspace {
+                    ui.weak(workspace.display().to_string());
+                }
+            });
             ui.add_space(6.);
         });
 
         egui::SidePanel::left("side_panel").show(ctx, |ui| {
-            ui.heading("Collections");
+            ui.horizontal(|ui| {
+                ui.heading("Collections");
+                ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("Import").on_hover_text("Import a collection from a JSON file").clicked() {
+                        self.import_collection();
+                    }
+                });
+            });
             ui.separator();
             
             ui.horizontal(|ui| {
@@ -109,92 +758,14 @@ impl eframe::App for PacketsApp {
                 });
             });
             ui.separator();
+            self.sidebar.render_search(ui, &self.collections.borrow(), &mut sidebar_actions);
             ScrollArea::vertical()
                 .show(ui, |ui| {
-                    for (i, collection) in self.collections.borrow_mut().iter_mut().enumerate() {
-                        let id = ui.make_persistent_id(format!("collection_{}", collection.uuid.to_string()));
-                        CollapsingState::load_with_default_open(ui.ctx(), id, true)
-                            .show_header(ui, |ui| {
-                                let selected = if let Some(c) = self.selected_collection {
-                                    c == i
-                                } else {
-                                    false
-                                };
-                                
-                                let label = egui::SelectableLabel::new(selected, &collection.name);
-                                
-                                ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
-                                    if ui.button("+").clicked() {
-                                        collection.create_request();
-                                    }
-                                    if ui.add_sized(ui.available_size(), label).clicked() {
-                                        if let Some(tab_location) = self.dock_state.find_tab(&collection.uuid) {
-                                            self.dock_state.set_active_tab(tab_location);
-                                        } else {
-                                            self.dock_state.push_to_focused_leaf(collection.uuid);
-                                        }
-                                        
-                                        self.selected_collection = Some(i);
-                                        self.selected_request = None;
-                                    }
-                                });
-                            })
-                            .body(|ui| {
-                               for (j, request) in collection.requests.iter().enumerate() {
-                                    let selected = if let Some(r) = self.selected_request {
-                                        r == j
-                                    } else {
-                                        false
-                                    };
-                                    let label = egui::SelectableLabel::new(selected, request.name());
-                                    
-                                    let resp = ui.add(label);
-                                    if resp.clicked() {
-                                        if let Some(tab_location) = self.dock_state.find_tab(&request.uuid) {
-                                            self.dock_state.set_active_tab(tab_location);
-                                        } else {
-                                            self.tab_viewer.requests.insert(request.uuid, request.clone());
-                                            self.dock_state.push_to_focused_leaf(request.uuid);
-                                        }
-                                        
-                                        
-                                        self.selected_collection = Some(i);
-                                        self.selected_request = Some(j);
-                                    }
-                                    resp.context_menu(|ui| {
-                                        if ui.button("Duplicate Request").clicked() {
-                                            // TODO: Implement Duplicating Tabs
-                                            let new_request = request.duplicate();
-                                            let uuid = new_request.uuid.clone();
-                                            self.tab_viewer.requests.insert(uuid, new_request);
-                                            self.dock_state.push_to_focused_leaf(uuid);
-                                            
-                                            ui.close_menu();
-                                        }
-                                        if ui.button("Delete").clicked() {
-                                            if let Some(tab_location) = self.dock_state.find_tab(&request.uuid) {
-                                                self.dock_state.remove_tab(tab_location);
-                                            }
-                                            self.tab_viewer.requests.remove(&request.uuid);
-                                            delete_request = Some((i, j));
-                                            
-                                            ui.close_menu();
-                                        }
-                                    });
-                                } 
-                            });
-                        ui.separator();
-                    }
+                    self.sidebar.render(ui, &self.collections.borrow(), &mut sidebar_actions);
                 });
         });
-        
-        if ctx.input_mut(|i| i.consume_shortcut(&egui::KeyboardShortcut { modifiers: Modifiers::CTRL, key: Key::S })) {
-            let leaf = self.dock_state.find_active_focused();
-            if let Some((_, tab)) = leaf {
-                if let Some(request) = self.tab_viewer.requests.get_mut(tab) {
-                    request.wants_save = true;
-                }
-            }
+        for action in sidebar_actions {
+            self.apply_sidebar_action(action);
         }
         
         let mut dock_style = Style::from_egui(&ctx.style());
@@ -220,9 +791,50 @@ impl eframe::App for PacketsApp {
         DockArea::new(&mut self.dock_state)
             .style(dock_style)
             .show(ctx, &mut self.tab_viewer);
+        self.render_save_to_collection(ctx);
+        if let Some(request) = self.tab_viewer.requests.values_mut().find(|r| r.wants_compare) {
+            request.wants_compare = false;
+            self.diff_window.compare_latest(request);
+        }
+        self.diff_window.render(ctx, &self.tab_viewer.requests);
+        self.render_confirm_close(ctx);
+        self.render_recovered_drafts(ctx);
+        self.render_confirm_quit(ctx, frame);
+        self.render_confirm_open(ctx);
+        self.keybindings_window.render(ctx, &mut self.keybindings);
         
-        if let Some((collection_index, request_index)) = delete_request {
-            self.tab_viewer.collections.borrow_mut()[collection_index].requests.remove(request_index);
+        // The entries are only needed while the palette is open
+        let entries = match self.command_palette.is_open() {
+            true => self.palette_entries(),
+            false => vec![],
+        };
+        match self.command_palette.render(ctx, &entries, &self.keybindings) {
+            Some(PaletteEntry::Command(command)) => self.run_command(ctx, command),
+            Some(PaletteEntry::Environment { collection, name, .. }) => {
+                if let Some(collection) = self.collections.borrow_mut().iter_mut().find(|c| c.uuid == collection) {
+                    collection.select_environment(name);
+                }
+            },
+            None => {},
         }
     }
 }
+
+/// What to do with unsaved changes
+enum CloseChoice {
+    Save,
+    Discard,
+    Cancel,
+}
+
+/// Lists the folders as places to save a request, indented below their parent
+fn render_folder_targets(ui: &mut egui::Ui, collection: Uuid, items: &Items, target: &mut Option<(Uuid, Option<Uuid>)>) {
+    ui.indent(("save_targets", collection), |ui| {
+        for folder in &items.folders {
+            if ui.selectable_label(false, &folder.name).clicked() {
+                *target = Some((collection, Some(folder.uuid)));
+            }
+            render_folder_targets(ui, collection, &folder.items, target);
+        }
+    });
+}
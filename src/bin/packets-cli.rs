@@ -0,0 +1,7 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+fn main() -> std::process::ExitCode {
+    packets::cli::main()
+}
@@ -0,0 +1,337 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Runs exported collections or a workspace without a window, for scripts and CI.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use anyhow::{anyhow, bail};
+use serde::Serialize;
+
+use crate::collection::CollectionFile;
+use crate::engine;
+use crate::workspace::Workspace;
+
+const USAGE: &str = "\
+Usage: packets-cli <collection.json | workspace directory> [options]
+
+Sends every request of an exported collection, or of every collection in a
+workspace, and checks their tests.
+
+Options:
+    -e, --env <name>       Use the variables of this environment
+        --stop-on-failure  Skip the remaining requests after the first failure
+        --json             Print the results as JSON. A workspace prints a list
+                           with one entry per collection
+    -k, --insecure         Accept invalid TLS certificates, e.g. self-signed ones
+    -h, --help             Show this help
+
+Exits with 0 if every request passed, 1 if one failed and 2 on usage errors or
+if the collection couldn't be loaded.";
+
+/// Every request passed
+const EXIT_PASSED: u8 = 0;
+/// A request failed or couldn't be sent
+const EXIT_FAILED: u8 = 1;
+/// The arguments were wrong or the collection couldn't be loaded
+const EXIT_USAGE: u8 = 2;
+
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    collection: PathBuf,
+    environment: Option<String>,
+    stop_on_failure: bool,
+    json: bool,
+    insecure: bool,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Option<Self>> {
+        let mut collection = None;
+        let mut environment = None;
+        let mut stop_on_failure = false;
+        let mut json = false;
+        let mut insecure = false;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-e" | "--env" => {
+                    environment = Some(args.next().ok_or_else(|| anyhow!("{} needs the name of an environment", arg))?);
+                },
+                "--stop-on-failure" => stop_on_failure = true,
+                "--json" => json = true,
+                "-k" | "--insecure" => insecure = true,
+                _ if arg.starts_with('-') => bail!("Unknown option {}", arg),
+                _ if collection.is_none() => collection = Some(PathBuf::from(arg)),
+                _ => bail!("Unexpected argument {}", arg),
+            }
+        }
+
+        let collection = collection.ok_or_else(|| anyhow!("No collection file given"))?;
+        Ok(Some(Self { collection, environment, stop_on_failure, json, insecure }))
+    }
+}
+
+#[derive(Serialize)]
+struct TestReport {
+    passed: bool,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct RequestReport {
+    name: String,
+    /// `passed`, `failed`, `error` or `skipped`
+    result: &'static str,
+    status: Option<u16>,
+    duration_ms: Option<u128>,
+    error: Option<String>,
+    tests: Vec<TestReport>,
+}
+
+#[derive(Serialize)]
+struct Report {
+    collection: String,
+    environment: Option<String>,
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+    requests: Vec<RequestReport>,
+}
+
+impl Report {
+    fn print_human(&self) {
+        println!("{}", self.collection);
+        if let Some(environment) = &self.environment {
+            println!("Environment: {}", environment);
+        }
+        println!();
+        for request in &self.requests {
+            let symbol = match request.result {
+                "passed" => "✔",
+                "skipped" => "-",
+                _ => "✘",
+            };
+            let status = request.status.map(|s| s.to_string()).unwrap_or_default();
+            let duration = request.duration_ms.map(|d| format!("{} ms", d)).unwrap_or_default();
+            println!("{} {:<40} {:>3} {:>8}", symbol, request.name, status, duration);
+            if let Some(error) = &request.error {
+                println!("    {}", error);
+            }
+            for test in request.tests.iter().filter(|t| !t.passed) {
+                println!("    ✘ {}", test.message);
+            }
+        }
+        println!();
+        println!("{} passed, {} failed, {} skipped", self.passed, self.failed, self.skipped);
+    }
+}
+
+/// Loads the collections and selects the environment in every collection that has it
+fn load(options: &Options) -> anyhow::Result<Vec<CollectionFile>> {
+    let mut collections = match options.collection.is_dir() {
+        true => Workspace::load(&options.collection)?.collections,
+        false => vec![CollectionFile::load(&options.collection)?],
+    };
+    if let Some(environment) = &options.environment {
+        let mut found = false;
+        for collection in collections.iter_mut() {
+            if collection.data.environments.iter().any(|e| &e.name == environment) {
+                collection.data.selected_environment = Some(environment.clone());
+                found = true;
+            }
+        }
+        if !found {
+            bail!("There is no environment called \"{}\"", environment);
+        }
+    }
+    Ok(collections)
+}
+
+fn run(client: &reqwest::blocking::Client, file: CollectionFile, options: &Options) -> Report {
+    let mut plan = file.plan();
+    let mut collection_data = file.data;
+    let mut requests = vec![];
+    let mut failed = false;
+    for i in 0..plan.requests.len() {
+        let mut report = RequestReport {
+            name: plan.requests[i].0.name.clone(),
+            result: "skipped",
+            status: None,
+            duration_ms: None,
+            error: None,
+            tests: vec![],
+        };
+        if failed && options.stop_on_failure {
+            requests.push(report);
+            continue;
+        }
+
+        match plan.run(client, i, &mut collection_data, &mut vec![]) {
+            Ok(outcome) => {
+                report.result = if outcome.passed() { "passed" } else { "failed" };
+                report.status = Some(outcome.response.status);
+                report.duration_ms = Some(outcome.response.duration.as_millis());
+                report.tests = outcome.test_results.into_iter()
+                    .map(|r| TestReport { passed: r.passed, message: r.message })
+                    .collect();
+            },
+            Err(e) => {
+                report.result = "error";
+                report.error = Some(format!("{:#}", e));
+            },
+        }
+        failed |= report.result != "passed";
+        requests.push(report);
+    }
+
+    let count = |result: &str| requests.iter().filter(|r| r.result == result).count();
+    Report {
+        collection: file.name,
+        environment: collection_data.selected_environment,
+        passed: count("passed"),
+        failed: count("failed") + count("error"),
+        skipped: count("skipped"),
+        requests,
+    }
+}
+
+/// Runs the command line interface with the arguments after the program name and returns the exit code
+fn run_cli(args: impl Iterator<Item = String>) -> u8 {
+    let options = match Options::parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return EXIT_PASSED;
+        },
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return EXIT_USAGE;
+        },
+    };
+
+    let client = match options.insecure {
+        true => engine::client(),
+        false => engine::verifying_client(),
+    };
+    let loaded = load(&options).and_then(|collections| Ok((client?, collections)));
+    let (client, collections) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            return EXIT_USAGE;
+        },
+    };
+    let reports: Vec<Report> = collections.into_iter().map(|file| run(&client, file, &options)).collect();
+
+    if options.json {
+        let json = match options.collection.is_dir() {
+            true => serde_json::to_string_pretty(&reports),
+            false => serde_json::to_string_pretty(&reports[0]),
+        };
+        match json {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return EXIT_USAGE;
+            },
+        }
+    } else {
+        for (i, report) in reports.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            report.print_human();
+        }
+    }
+
+    match reports.iter().all(|r| r.failed == 0) {
+        true => EXIT_PASSED,
+        false => EXIT_FAILED,
+    }
+}
+
+/// Runs the command line interface. Exits with 1 if a request failed and with 2 on usage errors
+pub fn main() -> ExitCode {
+    ExitCode::from(run_cli(std::env::args().skip(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collection::CollectionData;
+    use crate::request::RequestData;
+    use crate::request::assertions::Assertion;
+    use crate::test_support::{self, stand_in};
+
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter().map(|a| a.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    /// Saves a collection with one request that expects a 200 into a temporary file
+    fn collection_file(address: &str) -> PathBuf {
+        let file = CollectionFile {
+            name: String::from("API"),
+            data: CollectionData::default(),
+            folders: vec![],
+            requests: vec![RequestData {
+                url_string: format!("http://{}/", address),
+                assertions: vec![Assertion::StatusEquals { status: 200 }],
+                ..Default::default()
+            }],
+        };
+        let path = std::env::temp_dir().join(format!("packets-cli-{}.json", uuid::Uuid::new_v4()));
+        file.save(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = Options::parse(args(&["api.json", "-e", "Dev", "--stop-on-failure", "--json", "-k"])).unwrap().unwrap();
+        assert_eq!(options, Options {
+            collection: PathBuf::from("api.json"),
+            environment: Some(String::from("Dev")),
+            stop_on_failure: true,
+            json: true,
+            insecure: true,
+        });
+        let options = Options::parse(args(&["--env", "Prod", "workspace"])).unwrap().unwrap();
+        assert_eq!(options.environment.as_deref(), Some("Prod"));
+        assert!(!options.insecure);
+        assert_eq!(Options::parse(args(&["api.json", "--help"])).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_wrong_arguments() {
+        let error = |arguments: &[&str]| Options::parse(args(arguments)).unwrap_err().to_string();
+        assert_eq!(error(&["api.json", "--verbose"]), "Unknown option --verbose");
+        assert_eq!(error(&["api.json", "other.json"]), "Unexpected argument other.json");
+        assert_eq!(error(&["api.json", "--env"]), "--env needs the name of an environment");
+        assert_eq!(error(&[]), "No collection file given");
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(run_cli(args(&["--help"])), EXIT_PASSED);
+        assert_eq!(run_cli(args(&["--verbose"])), EXIT_USAGE);
+        assert_eq!(run_cli(args(&["/does/not/exist.json"])), EXIT_USAGE);
+
+        let passing = collection_file(&stand_in(|_| test_support::status(200)).0);
+        let failing = collection_file(&stand_in(|_| test_support::status(500)).0);
+        let passing_path = passing.to_string_lossy().to_string();
+        let failing_path = failing.to_string_lossy().to_string();
+        let passed = run_cli(args(&[&passing_path]));
+        let failed = run_cli(args(&[&failing_path, "--json"]));
+        let unknown_environment = run_cli(args(&[&passing_path, "--env", "Prod"]));
+        std::fs::remove_file(passing).unwrap();
+        std::fs::remove_file(failing).unwrap();
+
+        assert_eq!(passed, EXIT_PASSED);
+        assert_eq!(failed, EXIT_FAILED);
+        assert_eq!(unknown_environment, EXIT_USAGE);
+    }
+}
@@ -0,0 +1,398 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use egui::{Ui, Layout, Align, Id, TopBottomPanel};
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+use crate::request::{Request, RequestData};
+use crate::tabs::auth::{AuthType, AuthData};
+
+use super::{CollectionData, RunPlan, render_variables};
+
+/// What a folder adds on top of its parent for the requests inside it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FolderData {
+    /// `Inherit` uses the authorization of the parent folder or the collection
+    pub selected_auth: AuthType,
+    pub auth: BTreeMap<AuthType, AuthData>,
+    /// Override the variables of the parent folder and the collection
+    #[serde(default)]
+    pub variables: Vec<(String, String)>,
+    /// Sent with every request in the folder, unless the request sets the header itself
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+}
+
+impl Default for FolderData {
+    fn default() -> Self {
+        Self {
+            selected_auth: AuthType::Inherit,
+            auth: BTreeMap::new(),
+            variables: vec![],
+            headers: vec![],
+        }
+    }
+}
+
+/// A folder in a collection file
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FolderFile {
+    pub name: String,
+    pub data: FolderData,
+    #[serde(default)]
+    pub folders: Vec<FolderFile>,
+    #[serde(default)]
+    pub requests: Vec<RequestData>,
+}
+
+impl FolderFile {
+    pub(super) fn plan(&self, plan: &mut RunPlan, path: &mut Vec<usize>) {
+        path.push(plan.folders.len());
+        plan.folders.push(self.data.clone());
+        plan_items(&self.folders, &self.requests, plan, path);
+        path.pop();
+    }
+}
+
+/// Adds the requests to the plan in the order they are shown, folders first
+pub(super) fn plan_items(folders: &[FolderFile], requests: &[RequestData], plan: &mut RunPlan, path: &mut Vec<usize>) {
+    for folder in folders {
+        folder.plan(plan, path);
+    }
+    for request_data in requests {
+        plan.requests.push((request_data.clone(), path.clone()));
+    }
+}
+
+/// The folders and requests directly inside a collection or folder
+#[derive(Serialize, Deserialize, Default)]
+pub struct Items {
+    #[serde(default)]
+    pub folders: Vec<Folder>,
+    #[serde(default)]
+    pub requests: Vec<Request>,
+}
+
+impl Items {
+    pub fn find_request(&self, uuid: &Uuid) -> Option<&Request> {
+        self.requests.iter().find(|r| &r.uuid == uuid)
+            .or_else(|| self.folders.iter().find_map(|f| f.items.find_request(uuid)))
+    }
+
+    pub fn find_request_mut(&mut self, uuid: &Uuid) -> Option<&mut Request> {
+        if let Some(request) = self.requests.iter_mut().find(|r| &r.uuid == uuid) {
+            return Some(request);
+        }
+        self.folders.iter_mut().find_map(|f| f.items.find_request_mut(uuid))
+    }
+
+    pub fn find_folder(&self, uuid: &Uuid) -> Option<&Folder> {
+        self.folders.iter().find(|f| &f.uuid == uuid)
+            .or_else(|| self.folders.iter().find_map(|f| f.items.find_folder(uuid)))
+    }
+
+    pub fn find_folder_mut(&mut self, uuid: &Uuid) -> Option<&mut Folder> {
+        if let Some(i) = self.folders.iter().position(|f| &f.uuid == uuid) {
+            return Some(&mut self.folders[i]);
+        }
+        self.folders.iter_mut().find_map(|f| f.items.find_folder_mut(uuid))
+    }
+
+    /// The items of the folder, or these items if there is no folder
+    pub fn items_mut(&mut self, folder: Option<&Uuid>) -> Option<&mut Items> {
+        match folder {
+            Some(uuid) => self.find_folder_mut(uuid).map(|f| &mut f.items),
+            None => Some(self),
+        }
+    }
+
+    pub fn remove_request(&mut self, uuid: &Uuid) -> Option<Request> {
+        if let Some(i) = self.requests.iter().position(|r| &r.uuid == uuid) {
+            return Some(self.requests.remove(i));
+        }
+        self.folders.iter_mut().find_map(|f| f.items.remove_request(uuid))
+    }
+
+    /// Puts the request in front of `before`, or at the end if there is no such request
+    pub fn insert_request(&mut self, request: Request, before: Option<&Uuid>) {
+        let index = before
+            .and_then(|before| self.requests.iter().position(|r| &r.uuid == before))
+            .unwrap_or(self.requests.len());
+        self.requests.insert(index, request);
+    }
+
+    pub fn remove_folder(&mut self, uuid: &Uuid) -> Option<Folder> {
+        if let Some(i) = self.folders.iter().position(|f| &f.uuid == uuid) {
+            return Some(self.folders.remove(i));
+        }
+        self.folders.iter_mut().find_map(|f| f.items.remove_folder(uuid))
+    }
+
+    /// The number of requests in here, including the ones in folders
+    pub fn request_count(&self) -> usize {
+        self.requests.len() + self.folders.iter().map(|f| f.items.request_count()).sum::<usize>()
+    }
+
+    /// The UUIDs of every folder and request in here, including nested ones
+    pub fn uuids(&self) -> Vec<Uuid> {
+        let mut uuids: Vec<Uuid> = self.requests.iter().map(|r| r.uuid).collect();
+        for folder in &self.folders {
+            uuids.push(folder.uuid);
+            uuids.extend(folder.items.uuids());
+        }
+        uuids
+    }
+
+    /// The data of the folders a request is in, outermost first
+    pub fn scope_of(&self, uuid: &Uuid) -> Option<Vec<Rc<RefCell<FolderData>>>> {
+        if self.requests.iter().any(|r| &r.uuid == uuid) {
+            return Some(vec![]);
+        }
+        self.folders.iter().find_map(|folder| {
+            let mut scope = folder.items.scope_of(uuid)?;
+            scope.insert(0, Rc::clone(&folder.data));
+            Some(scope)
+        })
+    }
+
+    /// Points every request at the data of the collection and the folders it is in
+    pub fn relink(&mut self, collection_data: &Rc<RefCell<CollectionData>>, scope: &mut Vec<Rc<RefCell<FolderData>>>) {
+        for request in self.requests.iter_mut() {
+            request.set_scope(Rc::clone(collection_data), scope.clone());
+        }
+        for folder in self.folders.iter_mut() {
+            scope.push(Rc::clone(&folder.data));
+            folder.items.relink(collection_data, scope);
+            scope.pop();
+        }
+    }
+
+    /// Adds the requests to the plan in the order they are shown, folders first.
+    /// Returns the UUIDs of the planned folders
+    pub fn plan(&self, plan: &mut RunPlan, path: &mut Vec<usize>) -> Vec<Uuid> {
+        let mut uuids = vec![];
+        for folder in &self.folders {
+            path.push(plan.folders.len());
+            plan.folders.push(folder.data.borrow().clone());
+            uuids.push(folder.uuid);
+            uuids.extend(folder.items.plan(plan, path));
+            path.pop();
+        }
+        for request in &self.requests {
+            plan.requests.push((request.request_data().clone(), path.clone()));
+        }
+        uuids
+    }
+
+    pub fn to_files(&self) -> (Vec<FolderFile>, Vec<RequestData>) {
+        let folders = self.folders.iter().map(Folder::to_file).collect();
+        let requests = self.requests.iter().map(|r| r.request_data().clone()).collect();
+        (folders, requests)
+    }
+
+    pub fn from_files(folders: Vec<FolderFile>, requests: Vec<RequestData>, collection_data: &Rc<RefCell<CollectionData>>) -> Self {
+        Self {
+            folders: folders.into_iter().map(|f| Folder::from_file(f, collection_data)).collect(),
+            requests: requests.into_iter()
+                .map(|request_data| Request::with_data(request_data, Rc::clone(collection_data)))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+enum FolderTab {
+    Auth,
+    Variables,
+    Headers,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Folder {
+    pub uuid: Uuid,
+    pub name: String,
+    pub data: Rc<RefCell<FolderData>>,
+    #[serde(flatten)]
+    pub items: Items,
+
+    tab: FolderTab,
+}
+
+impl Folder {
+    pub fn new(name: String) -> Self {
+        Self {
+            uuid: Uuid::new_v4(),
+            name,
+            data: Default::default(),
+            items: Default::default(),
+            tab: FolderTab::Auth,
+        }
+    }
+
+    pub fn to_file(&self) -> FolderFile {
+        let (folders, requests) = self.items.to_files();
+        FolderFile {
+            name: self.name.clone(),
+            data: self.data.borrow().clone(),
+            folders,
+            requests,
+        }
+    }
+
+    fn from_file(file: FolderFile, collection_data: &Rc<RefCell<CollectionData>>) -> Self {
+        let mut folder = Self::new(file.name);
+        *folder.data.borrow_mut() = file.data;
+        folder.items = Items::from_files(file.folders, file.requests, collection_data);
+        folder
+    }
+
+    pub fn render(&mut self, ui: &mut Ui) {
+        TopBottomPanel::top(format!("folder_top_panel_{}", self.uuid)).resizable(true).show_inside(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading("Folder");
+                ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
+                    ui.text_edit_singleline(&mut self.name);
+                });
+            });
+            ui.add_space(10.);
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.tab, FolderTab::Auth, "Authorization");
+                ui.selectable_value(&mut self.tab, FolderTab::Variables, "Variables");
+                ui.selectable_value(&mut self.tab, FolderTab::Headers, "Headers");
+            });
+
+            let mut data = self.data.borrow_mut();
+            match &self.tab {
+                FolderTab::Auth => {
+                    egui::ComboBox::from_id_source("folder_auth_method")
+                        .selected_text(data.selected_auth.to_string())
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut data.selected_auth, AuthType::Inherit, "Inherit");
+                            ui.selectable_value(&mut data.selected_auth, AuthType::None, "None");
+                            ui.selectable_value(&mut data.selected_auth, AuthType::Basic, "Basic");
+                            ui.selectable_value(&mut data.selected_auth, AuthType::Bearer, "Bearer Token");
+                            ui.selectable_value(&mut data.selected_auth, AuthType::Jwt, "JWT Bearer");
+                        });
+                    let FolderData { selected_auth, auth, .. } = &mut *data;
+                    selected_auth.render(auth, ui);
+                },
+                FolderTab::Variables => {
+                    ui.weak("Override the variables of the collection for the requests in this folder");
+                    render_variables(ui, Id::new("folder_variables").with(self.uuid), &mut data.variables);
+                },
+                FolderTab::Headers => {
+                    ui.weak("Sent with every request in this folder, unless the request sets the header itself");
+                    render_variables(ui, Id::new("folder_headers").with(self.uuid), &mut data.headers);
+                },
+            }
+            ui.add_space(10.);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(name: &str) -> Request {
+        let request_data = RequestData { name: name.to_owned(), ..Default::default() };
+        Request::with_data(request_data, Rc::new(RefCell::new(Default::default())))
+    }
+
+    /// `Top` and a folder `Outer` with `Middle` and a folder `Inner` with `Deep` in it
+    fn items() -> (Items, [Uuid; 3], [Uuid; 2]) {
+        let mut inner = Folder::new(String::from("Inner"));
+        inner.items.requests.push(request("Deep"));
+        let mut outer = Folder::new(String::from("Outer"));
+        outer.items.requests.push(request("Middle"));
+        outer.items.folders.push(inner);
+        let items = Items { folders: vec![outer], requests: vec![request("Top")] };
+
+        let outer = &items.folders[0];
+        let inner = &outer.items.folders[0];
+        let requests = [items.requests[0].uuid, outer.items.requests[0].uuid, inner.items.requests[0].uuid];
+        let folders = [outer.uuid, inner.uuid];
+        (items, requests, folders)
+    }
+
+    #[test]
+    fn finds_and_removes_nested_items() {
+        let (mut items, [top, middle, deep], [outer, inner]) = items();
+
+        assert_eq!(items.find_request(&deep).unwrap().name(), "Deep");
+        assert_eq!(items.find_folder(&inner).unwrap().name, "Inner");
+        assert!(items.find_request(&Uuid::new_v4()).is_none());
+        items.find_folder_mut(&inner).unwrap().name = String::from("Renamed");
+        assert_eq!(items.find_folder(&inner).unwrap().name, "Renamed");
+        assert_eq!(items.request_count(), 3);
+        assert_eq!(items.uuids(), vec![top, outer, middle, inner, deep]);
+
+        assert_eq!(items.remove_request(&deep).unwrap().uuid, deep);
+        assert!(items.find_request(&deep).is_none());
+        assert!(items.remove_request(&deep).is_none());
+        assert_eq!(items.remove_folder(&inner).unwrap().uuid, inner);
+        assert!(items.find_folder(&inner).is_none());
+        assert!(items.items_mut(Some(&inner)).is_none());
+        assert_eq!(items.items_mut(Some(&outer)).unwrap().requests[0].uuid, middle);
+    }
+
+    #[test]
+    fn inserts_requests_in_front_of_others() {
+        let (mut items, [top, ..], _) = items();
+        let first = request("First");
+        let first_uuid = first.uuid;
+        items.insert_request(first, Some(&top));
+        let last = request("Last");
+        let last_uuid = last.uuid;
+        items.insert_request(last, Some(&Uuid::new_v4()));
+        items.insert_request(request("End"), None);
+
+        let names: Vec<String> = items.requests.iter().map(|r| r.name()).collect();
+        assert_eq!(names, vec!["First", "Top", "Last", "End"]);
+        assert_eq!(items.requests[0].uuid, first_uuid);
+        assert_eq!(items.requests[2].uuid, last_uuid);
+    }
+
+    #[test]
+    fn relinked_requests_use_the_data_of_their_folders() {
+        let (mut items, [top, middle, deep], _) = items();
+        let collection_data = Rc::new(RefCell::new(CollectionData::default()));
+        items.relink(&collection_data, &mut vec![]);
+
+        let outer = Rc::clone(&items.folders[0].data);
+        let inner = Rc::clone(&items.folders[0].items.folders[0].data);
+        // Every request holds the collection data and the data of the folders it is in
+        assert_eq!(Rc::strong_count(&collection_data), 4);
+        assert_eq!(Rc::strong_count(&outer), 4);
+        assert_eq!(Rc::strong_count(&inner), 3);
+
+        assert_eq!(items.scope_of(&top).unwrap().len(), 0);
+        let scope = items.scope_of(&middle).unwrap();
+        assert!(scope.len() == 1 && Rc::ptr_eq(&scope[0], &outer));
+        let scope = items.scope_of(&deep).unwrap();
+        assert!(scope.len() == 2 && Rc::ptr_eq(&scope[0], &outer) && Rc::ptr_eq(&scope[1], &inner));
+        assert!(items.scope_of(&Uuid::new_v4()).is_none());
+    }
+
+    #[test]
+    fn plans_folders_before_requests() {
+        let (items, _, [outer, inner]) = items();
+        items.folders[0].data.borrow_mut().variables.push((String::from("page"), String::from("1")));
+        let mut plan = RunPlan::default();
+
+        let folders = items.plan(&mut plan, &mut vec![]);
+
+        assert_eq!(folders, vec![outer, inner]);
+        assert_eq!(plan.folders[0].variables, vec![(String::from("page"), String::from("1"))]);
+        let planned: Vec<(&str, &[usize])> = plan.requests.iter().map(|(r, path)| (r.name.as_str(), path.as_slice())).collect();
+        assert_eq!(planned, vec![("Deep", &[0, 1][..]), ("Middle", &[0][..]), ("Top", &[][..])]);
+    }
+}
@@ -0,0 +1,125 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! A lenient reading of the saved app state. It is used when the state doesn't load anymore,
+//! so the collections, folders and requests in it aren't lost with the rest
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::request::tabs::body_tab::{BodyData, BodyType};
+use crate::request::{Request, RequestData, RequestMethod};
+
+use super::{Collection, Environment, Folder};
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct LegacyState {
+    collections: Vec<LegacyCollection>,
+}
+
+impl LegacyState {
+    pub fn into_collections(self) -> Vec<Collection> {
+        self.collections.into_iter().map(LegacyCollection::into_collection).collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct LegacyCollection {
+    uuid: Uuid,
+    name: String,
+    #[serde(default)]
+    data: LegacyData,
+    #[serde(default)]
+    folders: Vec<LegacyFolder>,
+    #[serde(default)]
+    requests: Vec<LegacyRequest>,
+}
+
+impl LegacyCollection {
+    fn into_collection(self) -> Collection {
+        let mut collection = Collection::new(self.name);
+        collection.uuid = self.uuid;
+        {
+            let mut data = collection.data.borrow_mut();
+            data.variables = self.data.variables;
+            data.environments = self.data.environments;
+            data.headers = self.data.headers;
+        }
+        collection.items.folders = self.folders.into_iter().map(LegacyFolder::into_folder).collect();
+        collection.items.requests = self.requests.into_iter().map(LegacyRequest::into_request).collect();
+        collection.relink();
+        collection
+    }
+}
+
+/// Only the parts of the collection data that are plain lists. Auth settings are left out
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct LegacyData {
+    variables: Vec<(String, String)>,
+    environments: Vec<Environment>,
+    headers: Vec<(String, String)>,
+}
+
+#[derive(Deserialize)]
+struct LegacyFolder {
+    uuid: Uuid,
+    name: String,
+    #[serde(default)]
+    folders: Vec<LegacyFolder>,
+    #[serde(default)]
+    requests: Vec<LegacyRequest>,
+}
+
+impl LegacyFolder {
+    fn into_folder(self) -> Folder {
+        let mut folder = Folder::new(self.name);
+        folder.uuid = self.uuid;
+        folder.items.folders = self.folders.into_iter().map(LegacyFolder::into_folder).collect();
+        folder.items.requests = self.requests.into_iter().map(LegacyRequest::into_request).collect();
+        folder
+    }
+}
+
+#[derive(Deserialize)]
+struct LegacyRequest {
+    uuid: Uuid,
+    #[serde(default)]
+    request_data: LegacyRequestData,
+}
+
+impl LegacyRequest {
+    fn into_request(self) -> Request {
+        let data = self.request_data;
+        let request_data = RequestData {
+            name: data.name.unwrap_or_else(|| RequestData::default().name),
+            method: data.method,
+            url_string: data.url_string,
+            headers: data.headers,
+            body: data.body,
+            selected_body: data.selected_body,
+            ..Default::default()
+        };
+        // The collection hands out its data when it is relinked
+        let mut request = Request::with_data(request_data, Rc::new(RefCell::new(Default::default())));
+        request.uuid = self.uuid;
+        request
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct LegacyRequestData {
+    name: Option<String>,
+    method: RequestMethod,
+    url_string: String,
+    headers: Vec<(String, String)>,
+    body: BTreeMap<BodyType, BodyData>,
+    selected_body: BodyType,
+}
@@ -3,74 +3,733 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
 use std::rc::Rc;
 
-use egui::Ui;
+mod runner;
+mod folder;
+mod legacy;
+
+use anyhow::Context;
+use egui::{Ui, Button, TextEdit, Layout, Align, CollapsingHeader, Id};
 use egui::TopBottomPanel;
-use serde::Deserialize;
+use egui_extras::{TableBuilder, Column};
+use serde::{Deserialize, Deserializer};
 use serde::Serialize;
 use uuid::Uuid;
 
-use crate::tabs::auth::AuthType;
-use crate::request::Request;
+use crate::tabs::auth::{AuthType, AuthData};
+use crate::request::{Request, RequestData};
+use crate::request::tabs::script_tab::SCRIPT_HELP;
+
+use self::runner::CollectionRun;
+
+pub use self::folder::{Folder, FolderData, FolderFile, Items};
+pub use self::legacy::LegacyState;
+pub use self::runner::RunPlan;
 
 
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
 enum CollectionTab {
-    Auth
+    Auth,
+    Variables,
+    Headers,
+    Environments,
+    Script,
+    Runner,
+    Settings,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+/// A set of variables that overrides the collection variables while it is active
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Environment {
+    pub name: String,
+    pub variables: Vec<(String, String)>,
+}
+
+/// The data of a collection and of the folders a request is in, outermost folder first
+pub type Scope = (Rc<RefCell<CollectionData>>, Vec<Rc<RefCell<FolderData>>>);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub struct CollectionData {
     pub selected_auth: AuthType,
-    pub credentials: HashMap<String, String>,
+    #[serde(default)]
+    pub auth: BTreeMap<AuthType, AuthData>,
+    
+    /// Filled into `{{name}}` placeholders when a request is sent
+    #[serde(default)]
+    pub variables: Vec<(String, String)>,
+    #[serde(default)]
+    pub environments: Vec<Environment>,
+    /// The name of the active environment
+    #[serde(default)]
+    pub selected_environment: Option<String>,
+    /// Runs before the pre-request script of every request in the collection
+    #[serde(default)]
+    pub pre_request_script: String,
+    /// Sent with every request, unless the request sets the header itself
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
 }
 
+impl CollectionData {
+    pub fn active_environment(&self) -> Option<&Environment> {
+        let name = self.selected_environment.as_ref()?;
+        self.environments.iter().find(|e| &e.name == name)
+    }
+    
+    fn active_environment_mut(&mut self) -> Option<&mut Environment> {
+        let name = self.selected_environment.as_ref()?;
+        self.environments.iter_mut().find(|e| &e.name == name)
+    }
+    
+    /// The collection variables overridden by the ones of the active environment
+    pub fn resolved_variables(&self) -> BTreeMap<String, String> {
+        let mut variables: BTreeMap<String, String> = self.variables.iter().cloned().collect();
+        if let Some(environment) = self.active_environment() {
+            variables.extend(environment.variables.iter().cloned());
+        }
+        variables
+    }
+    
+    /// Stores the variables after a script changed them. Variables of the active environment are
+    /// updated in the environment, everything else in the collection variables
+    pub fn store_variables(&mut self, mut variables: BTreeMap<String, String>) {
+        let mut shadowed = BTreeSet::new();
+        if let Some(environment) = self.active_environment_mut() {
+            environment.variables.retain_mut(|(key, value)| match variables.remove(key) {
+                Some(new_value) => {
+                    *value = new_value;
+                    shadowed.insert(key.clone());
+                    true
+                },
+                None => false,
+            });
+        }
+        self.variables.retain_mut(|(key, value)| match variables.remove(key) {
+            Some(new_value) => {
+                *value = new_value;
+                true
+            },
+            None => shadowed.contains(key),
+        });
+        self.variables.extend(variables);
+    }
+    
+    /// The data a request inside these folders is sent with, outermost folder first.
+    /// Folder variables override the collection variables, the active environment still overrides both
+    pub fn scoped<'a>(&self, folders: impl IntoIterator<Item = &'a FolderData>) -> CollectionData {
+        let mut scoped = self.clone();
+        for folder in folders {
+            if folder.selected_auth != AuthType::Inherit {
+                scoped.selected_auth = folder.selected_auth.clone();
+                if let Some(auth) = folder.auth.get(&folder.selected_auth) {
+                    scoped.auth.insert(folder.selected_auth.clone(), auth.clone());
+                }
+            }
+            for (key, value) in &folder.variables {
+                set_pair(&mut scoped.variables, key, value, |a, b| a == b);
+            }
+            for (key, value) in &folder.headers {
+                set_pair(&mut scoped.headers, key, value, |a, b| a.eq_ignore_ascii_case(b));
+            }
+        }
+        scoped
+    }
+    
+    /// Takes back the variables and environments of data created with [`CollectionData::scoped`] after
+    /// a script changed them. Variables go to the innermost folder that defines them, or the collection
+    pub fn unscope<'a>(&mut self, folders: impl IntoIterator<Item = &'a mut FolderData>, scoped: CollectionData) {
+        let mut folders: Vec<&mut FolderData> = folders.into_iter().collect();
+        let variables: BTreeMap<String, String> = scoped.variables.into_iter().collect();
+        
+        for folder in folders.iter_mut() {
+            folder.variables.retain(|(key, _)| variables.contains_key(key));
+        }
+        self.variables.retain(|(key, _)| variables.contains_key(key));
+        for (key, value) in &variables {
+            match folders.iter_mut().rev().find(|f| f.variables.iter().any(|(k, _)| k == key)) {
+                Some(folder) => set_pair(&mut folder.variables, key, value, |a, b| a == b),
+                None => set_pair(&mut self.variables, key, value, |a, b| a == b),
+            }
+        }
+        self.environments = scoped.environments;
+    }
+}
 
-#[derive(Serialize, Deserialize, PartialEq, Eq)]
+/// Moves a request into a folder of a collection, in front of `before` or at the end.
+/// Does nothing if the request or the folder doesn't exist
+pub fn move_request(collections: &mut [Collection], request: &Uuid, collection: &Uuid, folder: Option<&Uuid>, before: Option<&Uuid>) {
+    if before == Some(request) {
+        return;
+    }
+    let target_exists = collections.iter_mut()
+        .find(|c| &c.uuid == collection)
+        .and_then(|c| c.items.items_mut(folder))
+        .is_some();
+    if !target_exists {
+        return;
+    }
+    let Some(moved) = collections.iter_mut().find_map(|c| c.items.remove_request(request)) else {
+        return;
+    };
+    if let Some(target) = collections.iter_mut().find(|c| &c.uuid == collection) {
+        if let Some(items) = target.items.items_mut(folder) {
+            items.insert_request(moved, before);
+        }
+        // The request uses the collection data and folders of its new place from now on
+        target.relink();
+    }
+}
+
+/// Replaces the value of the first pair with the key or appends a new pair
+fn set_pair(pairs: &mut Vec<(String, String)>, key: &str, value: &str, same_key: impl Fn(&str, &str) -> bool) {
+    match pairs.iter_mut().find(|(k, _)| same_key(k, key)) {
+        Some((_, v)) => *v = value.to_string(),
+        None => pairs.push((key.to_string(), value.to_string())),
+    }
+}
+
+/// The format collections are exported in. It only holds the data and no UI state
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CollectionFile {
+    pub name: String,
+    pub data: CollectionData,
+    #[serde(default)]
+    pub folders: Vec<FolderFile>,
+    pub requests: Vec<RequestData>,
+}
+
+impl CollectionFile {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+        serde_json::from_str(&file).with_context(|| format!("{} is not a valid collection file", path.display()))
+    }
+    
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let file = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, file).with_context(|| format!("Could not write {}", path.display()))
+    }
+    
+    /// Every request in the order they are shown, folders first
+    pub fn plan(&self) -> RunPlan {
+        let mut plan = RunPlan::default();
+        folder::plan_items(&self.folders, &self.requests, &mut plan, &mut vec![]);
+        plan
+    }
+}
+
+#[derive(Serialize)]
 pub struct Collection {
     pub uuid: Uuid,
     pub name: String,
-    pub requests: Vec<Request>,
+    #[serde(flatten)]
+    pub items: Items,
     
     data: Rc<RefCell<CollectionData>>,
     
     tab: CollectionTab,
+    
+    #[serde(default)]
+    stop_on_failure: bool,
+    #[serde(skip)]
+    run: Option<CollectionRun>,
+    /// The folders of the current run, in the order of [`RunPlan::folders`]
+    #[serde(skip)]
+    run_folders: Vec<Uuid>,
+}
+
+/// Requests don't serialize the collection data, so they are pointed at the collection's copy again
+impl<'de> Deserialize<'de> for Collection {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Fields {
+            uuid: Uuid,
+            name: String,
+            #[serde(flatten)]
+            items: Items,
+            data: CollectionData,
+            tab: CollectionTab,
+            #[serde(default)]
+            stop_on_failure: bool,
+        }
+        
+        let fields = Fields::deserialize(deserializer)?;
+        let mut collection = Self {
+            uuid: fields.uuid,
+            name: fields.name,
+            items: fields.items,
+            data: Rc::new(RefCell::new(fields.data)),
+            tab: fields.tab,
+            stop_on_failure: fields.stop_on_failure,
+            run: None,
+            run_folders: vec![],
+        };
+        collection.relink();
+        Ok(collection)
+    }
+}
+
+impl PartialEq for Collection {
+    fn eq(&self, other: &Self) -> bool {
+        self.uuid == other.uuid
+    }
 }
 
+impl Eq for Collection {}
+
 impl Collection {
-    // TODO: Implement auth and variables
     pub fn new(name: String) -> Self {
         Self {
             uuid: Uuid::new_v4(),
             name,
-            requests: vec![],
+            items: Default::default(),
             data: Rc::new(RefCell::new(Default::default())),
             tab: CollectionTab::Auth,
+            stop_on_failure: false,
+            run: None,
+            run_folders: vec![],
+        }
+    }
+    
+    /// Points the requests at the data of the collection and their folders again after they were moved or loaded
+    pub fn relink(&mut self) {
+        self.items.relink(&self.data, &mut vec![]);
+    }
+    
+    /// The collection data and the data of the folders the request is in, if it is in this collection
+    pub fn scope_of(&self, uuid: &Uuid) -> Option<Scope> {
+        let folders = self.items.scope_of(uuid)?;
+        Some((Rc::clone(&self.data), folders))
+    }
+    
+    pub fn to_file(&self) -> CollectionFile {
+        let (folders, requests) = self.items.to_files();
+        CollectionFile {
+            name: self.name.clone(),
+            data: self.data.borrow().clone(),
+            folders,
+            requests,
+        }
+    }
+    
+    /// A deep copy with new UUIDs for the collection, its folders and requests
+    pub fn duplicate(&self) -> Self {
+        let mut file = self.to_file();
+        file.name = format!("{} (Copy)", self.name);
+        Self::from_file(file)
+    }
+    
+    /// The names of the environments and the selected one
+    pub fn environments(&self) -> (Vec<String>, Option<String>) {
+        let data = self.data.borrow();
+        (data.environments.iter().map(|e| e.name.clone()).collect(), data.selected_environment.clone())
+    }
+    
+    pub fn select_environment(&mut self, name: Option<String>) {
+        self.data.borrow_mut().selected_environment = name;
+    }
+    
+    pub fn show_settings(&mut self) {
+        self.tab = CollectionTab::Settings;
+    }
+    
+    /// Creates a new collection from the file. Everything gets new UUIDs, so importing twice is fine
+    pub fn from_file(file: CollectionFile) -> Self {
+        let mut collection = Self::new(file.name);
+        *collection.data.borrow_mut() = file.data;
+        collection.items = Items::from_files(file.folders, file.requests, &collection.data);
+        collection.relink();
+        collection
+    }
+    
+    fn export(&self) {
+        let path = rfd::FileDialog::new()
+            .add_filter("Packets Collection", &["json"])
+            .set_file_name(&format!("{}.json", self.name))
+            .save_file();
+        if let Some(path) = path {
+            if let Err(e) = self.to_file().save(&path) {
+                log::error!("Exporting the collection failed: {:#}", e);
+            }
         }
     }
     
     pub fn render(&mut self, ui: &mut Ui) {
+        // Keep the variables the scripts set during a run, like after sending a single request
+        if let Some(changes) = self.run.as_ref().and_then(CollectionRun::take_changes) {
+            let folders: Vec<_> = self.run_folders.iter()
+                .map(|uuid| self.items.find_folder(uuid).map(|f| Rc::clone(&f.data)))
+                .collect();
+            let mut folders: Vec<_> = folders.iter().map(|f| f.as_ref().map(|f| f.borrow_mut())).collect();
+            changes.write_back(&mut self.data.borrow_mut(), folders.iter_mut().map(|f| f.as_deref_mut()));
+        }
         TopBottomPanel::top(format!("collection_top_panel_{}", self.uuid)).resizable(true).show_inside(ui, |ui| {
-            ui.heading(&self.name);
+            ui.horizontal(|ui| {
+                ui.heading(&self.name);
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    if ui.button("Export").clicked() {
+                        self.export();
+                    }
+                    let running = self.run.as_ref().map_or(false, |r| !r.is_finished());
+                    if ui.add_enabled(!running, Button::new("Run Collection")).clicked() {
+                        self.start_run(ui.ctx());
+                    }
+                    render_environment_selection(ui, &mut self.data.borrow_mut());
+                });
+            });
             ui.add_space(10.);
             
             ui.separator();
             ui.horizontal(|ui| {
                 ui.selectable_value(&mut self.tab, CollectionTab::Auth, "Authorization");
+                ui.selectable_value(&mut self.tab, CollectionTab::Variables, "Variables");
+                ui.selectable_value(&mut self.tab, CollectionTab::Headers, "Headers");
+                ui.selectable_value(&mut self.tab, CollectionTab::Environments, "Environments");
+                ui.selectable_value(&mut self.tab, CollectionTab::Script, "Pre-request Script");
+                ui.selectable_value(&mut self.tab, CollectionTab::Runner, "Runner");
+                ui.selectable_value(&mut self.tab, CollectionTab::Settings, "Settings");
             });
             
             match &self.tab {
                 CollectionTab::Auth => {
-                }
+                    let mut data = self.data.borrow_mut();
+                    egui::ComboBox::from_id_source("collection_auth_method")
+                        .selected_text(data.selected_auth.to_string())
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut data.selected_auth, AuthType::None, "None");
+                            ui.selectable_value(&mut data.selected_auth, AuthType::Basic, "Basic");
+                            ui.selectable_value(&mut data.selected_auth, AuthType::Bearer, "Bearer Token");
+                            ui.selectable_value(&mut data.selected_auth, AuthType::Jwt, "JWT Bearer");
+                        });
+                    let CollectionData { selected_auth, auth, .. } = &mut *data;
+                    selected_auth.render(auth, ui);
+                },
+                CollectionTab::Variables => {
+                    let id = Id::new("collection_variables").with(self.uuid);
+                    render_variables(ui, id, &mut self.data.borrow_mut().variables);
+                },
+                CollectionTab::Headers => {
+                    ui.weak("Sent with every request in the collection, unless the request sets the header itself");
+                    let id = Id::new("collection_headers").with(self.uuid);
+                    render_variables(ui, id, &mut self.data.borrow_mut().headers);
+                },
+                CollectionTab::Environments => {
+                    self.render_environments(ui);
+                },
+                CollectionTab::Script => {
+                    ui.weak(SCRIPT_HELP);
+                    ui.add_space(5.);
+                    let mut data = self.data.borrow_mut();
+                    let text_edit = TextEdit::multiline(&mut data.pre_request_script).code_editor();
+                    ui.add_sized(ui.available_size(), text_edit);
+                },
+                CollectionTab::Runner => {
+                    self.render_runner(ui);
+                },
+                CollectionTab::Settings => {
+                    ui.horizontal(|ui| {
+                        ui.label("Name");
+                        ui.text_edit_singleline(&mut self.name);
+                    });
+                    ui.checkbox(&mut self.stop_on_failure, "Stop runs on the first failure");
+                    ui.add_space(5.);
+                    let uuids = self.items.uuids();
+                    let requests = self.items.request_count();
+                    ui.weak(format!("{} folders, {} requests", uuids.len() - requests, requests));
+                },
             }
             ui.add_space(10.)
         });
     }
     
-    pub fn create_request(&mut self) {
-        self.requests.push(Request::new(Rc::clone(&self.data)))
+    fn render_environments(&mut self, ui: &mut Ui) {
+        let mut data = self.data.borrow_mut();
+        let mut remove_environment = None;
+        for (i, environment) in data.environments.iter_mut().enumerate() {
+            let id = Id::new("environment").with(self.uuid).with(i);
+            CollapsingHeader::new(&environment.name).id_source(id).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut environment.name);
+                    if ui.button("Delete").clicked() {
+                        remove_environment = Some(i);
+                    }
+                });
+                render_variables(ui, id.with("variables"), &mut environment.variables);
+            });
+        }
+        if let Some(i) = remove_environment {
+            let removed = data.environments.remove(i);
+            if data.selected_environment.as_ref() == Some(&removed.name) {
+                data.selected_environment = None;
+            }
+        }
+        if ui.button("Add Environment").clicked() {
+            let name = format!("Environment {}", data.environments.len() + 1);
+            data.environments.push(Environment { name, variables: vec![] });
+        }
+    }
+    
+    fn start_run(&mut self, ctx: &egui::Context) {
+        let mut plan = RunPlan::default();
+        self.run_folders = self.items.plan(&mut plan, &mut vec![]);
+        let collection_data = self.data.borrow().clone();
+        self.run = Some(CollectionRun::start(plan, collection_data, self.stop_on_failure, ctx.clone()));
+        self.tab = CollectionTab::Runner;
+    }
+    
+    fn render_runner(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.stop_on_failure, "Stop on first failure");
+            if let Some(run) = &self.run {
+                if !run.is_finished() && ui.button("Cancel").clicked() {
+                    run.cancel();
+                }
+            }
+        });
+        ui.add_space(5.);
+        
+        let Some(run) = &self.run else {
+            ui.label("Sends every request of the collection in order and checks their tests.");
+            return;
+        };
+        run.render(ui);
+    }
+    
+    /// Adds a new request to the folder, or to the top level if there is no folder
+    pub fn create_request(&mut self, folder: Option<&Uuid>) -> Option<Uuid> {
+        let request = Request::with_data(Default::default(), Rc::clone(&self.data));
+        let uuid = request.uuid;
+        self.items.items_mut(folder)?.requests.push(request);
+        self.relink();
+        Some(uuid)
+    }
+    
+    pub fn create_folder(&mut self, parent: Option<&Uuid>) -> Option<Uuid> {
+        let folder = Folder::new(String::from("New Folder"));
+        let uuid = folder.uuid;
+        self.items.items_mut(parent)?.folders.push(folder);
+        Some(uuid)
+    }
+}
+
+fn render_environment_selection(ui: &mut Ui, data: &mut CollectionData) {
+    if data.environments.is_empty() {
+        return;
+    }
+    egui::ComboBox::from_id_source("collection_environment")
+        .selected_text(data.selected_environment.clone().unwrap_or_else(|| String::from("No Environment")))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut data.selected_environment, None, "No Environment");
+            for environment in &data.environments {
+                ui.selectable_value(&mut data.selected_environment, Some(environment.name.clone()), &environment.name);
+            }
+        });
+}
+
+fn render_variables(ui: &mut Ui, id: Id, variables: &mut Vec<(String, String)>) {
+    let mut remove_variable = None;
+    let mut new_variable: (String, String) = ui.data_mut(|d| d.get_temp(id)).unwrap_or_default();
+    ui.push_id(id, |ui| TableBuilder::new(ui)
+        .column(Column::initial(128.).resizable(true))
+        .column(Column::initial(128.).resizable(true))
+        .column(Column::remainder())
+        .body(|mut body| {
+            for (i, (key, value)) in variables.iter_mut().enumerate() {
+                body.row(24., |mut row| {
+                    row.col(|ui| {
+                        ui.text_edit_singleline(key);
+                    });
+                    row.col(|ui| {
+                        ui.text_edit_singleline(value);
+                    });
+                    row.col(|ui| {
+                        let b = Button::new("x");
+                        if ui.add_sized(ui.available_size(), b).clicked() {
+                            remove_variable = Some(i);
+                        }
+                    });
+                });
+            }
+            body.row(24., |mut row| {
+                row.col(|ui| {
+                    ui.add(TextEdit::singleline(&mut new_variable.0).hint_text("Name"));
+                });
+                row.col(|ui| {
+                    ui.add(TextEdit::singleline(&mut new_variable.1).hint_text("Value"));
+                });
+            });
+        }));
+    if !new_variable.0.is_empty() || !new_variable.1.is_empty() {
+        variables.push(std::mem::take(&mut new_variable));
+    }
+    ui.data_mut(|d| d.insert_temp(id, new_variable));
+    if let Some(i) = remove_variable {
+        variables.remove(i);
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn requests_share_the_collection_data_after_a_round_trip() {
+        let mut collection = Collection::new(String::from("API"));
+        collection.create_request(None);
+        let folder = collection.create_folder(None).unwrap();
+        collection.create_request(Some(&folder));
+        collection.data.borrow_mut().variables.push((String::from("host"), String::from("localhost")));
+        
+        let json = serde_json::to_string(&collection).unwrap();
+        let loaded: Collection = serde_json::from_str(&json).unwrap();
+        
+        assert_eq!(loaded.uuid, collection.uuid);
+        assert_eq!(loaded.items.requests.len(), 1);
+        assert_eq!(loaded.items.folders[0].items.requests.len(), 1);
+        assert_eq!(*loaded.data.borrow(), *collection.data.borrow());
+        // The collection and both requests hold the same data
+        assert_eq!(Rc::strong_count(&loaded.data), 3);
+        // The request in the folder holds the data of the folder
+        assert_eq!(Rc::strong_count(&loaded.items.folders[0].data), 2);
+    }
+    
+    /// A request the way the first versions saved it
+    const BASELINE_REQUEST: &str = r#"{
+        "uuid": "6f1d2a59-3c36-4b7e-9a55-0a2c7f7c1f10",
+        "request_data": {
+            "name": "Users",
+            "method": "Get",
+            "url_string": "https://example.com/users",
+            "headers": [["Accept", "application/json"]],
+            "auth": {},
+            "selected_auth": "Inherit",
+            "body": {},
+            "selected_body": "None"
+        },
+        "tab": "Parameters",
+        "auth_tab": null,
+        "params_tab": {"parameters": [], "new_param": ["", ""]},
+        "headers_tab": {"new_header": ["", ""]},
+        "body_tab": null,
+        "wants_save": false,
+        "saved_data_hash": null
+    }"#;
+    
+    #[test]
+    fn loads_baseline_collection_data() {
+        let data: CollectionData = serde_json::from_str(r#"{"selected_auth": "None", "credentials": {}}"#).unwrap();
+        assert_eq!(data, CollectionData::default());
+    }
+    
+    #[test]
+    fn loads_baseline_collections() {
+        let json = format!(r#"{{
+            "uuid": "0b6c3a8e-2f0e-4d7a-8d0c-51d1a3b6a6e2",
+            "name": "API",
+            "requests": [{}],
+            "data": {{"selected_auth": "None", "credentials": {{}}}},
+            "tab": "Auth"
+        }}"#, BASELINE_REQUEST);
+        let collection: Collection = serde_json::from_str(&json).unwrap();
+        
+        assert_eq!(collection.name, "API");
+        assert_eq!(collection.items.requests.len(), 1);
+        let request = &collection.items.requests[0];
+        assert_eq!(request.name(), "Users");
+        assert_eq!(request.request_data().url_string, "https://example.com/users");
+        assert_eq!(Rc::strong_count(&collection.data), 2);
+    }
+    
+    #[test]
+    fn legacy_state_keeps_collections_the_app_state_cant_load() {
+        // Auth data the current types can't read
+        let broken = BASELINE_REQUEST.replace(r#""auth": {}"#, r#""auth": {"Basic": {"Digest": {}}}"#);
+        let json = format!(r#"{{
+            "collections": [{{
+                "uuid": "0b6c3a8e-2f0e-4d7a-8d0c-51d1a3b6a6e2",
+                "name": "API",
+                "data": {{"selected_auth": "None", "credentials": {{}}, "variables": [["host", "localhost"]]}},
+                "folders": [{{"uuid": "9e8f5f0a-7d7b-4d8e-b6a1-1f9c3a0e2d44", "name": "Admin", "requests": [{}]}}],
+                "tab": "Auth"
+            }}]
+        }}"#, broken);
+        assert!(serde_json::from_str::<Collection>(&json).is_err());
+        
+        let state: LegacyState = serde_json::from_str(&json).unwrap();
+        let collections = state.into_collections();
+        
+        assert_eq!(collections.len(), 1);
+        let collection = &collections[0];
+        assert_eq!(collection.name, "API");
+        assert_eq!(collection.data.borrow().variables, vec![(String::from("host"), String::from("localhost"))]);
+        let folder = &collection.items.folders[0];
+        assert_eq!(folder.name, "Admin");
+        let request = &folder.items.requests[0];
+        assert_eq!(request.uuid.to_string(), "6f1d2a59-3c36-4b7e-9a55-0a2c7f7c1f10");
+        assert_eq!(request.name(), "Users");
+        assert_eq!(request.request_data().headers, vec![(String::from("Accept"), String::from("application/json"))]);
+        // The request was relinked to the collection and the folder
+        assert_eq!(Rc::strong_count(&collection.data), 2);
+        assert_eq!(Rc::strong_count(&folder.data), 2);
+    }
+    
+    #[test]
+    fn moved_requests_take_the_scope_of_their_new_place() {
+        let mut api = Collection::new(String::from("API"));
+        let from = api.create_folder(None).unwrap();
+        let to = api.create_folder(None).unwrap();
+        let request = api.create_request(Some(&from)).unwrap();
+        let other = api.create_request(Some(&to)).unwrap();
+        let mut collections = vec![api, Collection::new(String::from("Other"))];
+        let (api_uuid, other_uuid) = (collections[0].uuid, collections[1].uuid);
+        let folder_data = |collections: &[Collection], uuid| Rc::clone(&collections[0].items.find_folder(&uuid).unwrap().data);
+        let (from_data, to_data) = (folder_data(&collections, from), folder_data(&collections, to));
+        assert_eq!((Rc::strong_count(&from_data), Rc::strong_count(&to_data)), (3, 3));
+
+        // In front of the request that is already there
+        move_request(&mut collections, &request, &api_uuid, Some(&to), Some(&other));
+        let target = collections[0].items.find_folder(&to).unwrap();
+        assert_eq!(target.items.requests[0].uuid, request);
+        assert!(collections[0].items.find_folder(&from).unwrap().items.requests.is_empty());
+        let (_, scope) = collections[0].scope_of(&request).unwrap();
+        assert!(scope.len() == 1 && Rc::ptr_eq(&scope[0], &to_data));
+        drop(scope);
+        // The request let go of the data of its old folder
+        assert_eq!((Rc::strong_count(&from_data), Rc::strong_count(&to_data)), (2, 4));
+
+        // Into another collection
+        move_request(&mut collections, &request, &other_uuid, None, None);
+        assert!(collections[0].items.find_request(&request).is_none());
+        let (data, scope) = collections[1].scope_of(&request).unwrap();
+        assert!(scope.is_empty() && Rc::ptr_eq(&data, &collections[1].data));
+        assert_eq!(Rc::strong_count(&to_data), 3);
+
+        // Places that don't exist leave the request where it is
+        move_request(&mut collections, &request, &api_uuid, Some(&Uuid::new_v4()), None);
+        move_request(&mut collections, &request, &other_uuid, None, Some(&request));
+        assert_eq!(collections[1].items.requests[0].uuid, request);
+    }
+    
+    #[test]
+    fn duplicates_are_deep_copies_with_new_uuids() {
+        let mut collection = Collection::new(String::from("API"));
+        collection.create_request(None);
+        let folder = collection.create_folder(None).unwrap();
+        collection.create_request(Some(&folder));
+        
+        let duplicate = collection.duplicate();
+        
+        assert_eq!(duplicate.name, "API (Copy)");
+        assert_ne!(duplicate.uuid, collection.uuid);
+        assert_eq!(duplicate.items.request_count(), 2);
+        let uuids = collection.items.uuids();
+        assert!(duplicate.items.uuids().iter().all(|uuid| !uuids.contains(uuid)));
+        // The copy doesn't share its data with the original
+        duplicate.data.borrow_mut().variables.push((String::from("host"), String::from("localhost")));
+        assert!(collection.data.borrow().variables.is_empty());
+        assert_eq!(Rc::strong_count(&duplicate.data), 3);
+    }
+}
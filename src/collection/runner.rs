@@ -0,0 +1,387 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use egui::{Ui, Color32, RichText};
+use egui_extras::{TableBuilder, Column};
+
+use reqwest::blocking::Client;
+
+use crate::collection::{set_pair, CollectionData, FolderData};
+use crate::request::RequestData;
+use crate::request::assertions::AssertionResult;
+use crate::engine::{self, Outcome};
+
+/// The requests of a collection in the order they are run, with the data of the folders they are in
+#[derive(Debug, Clone, Default)]
+pub struct RunPlan {
+    pub folders: Vec<FolderData>,
+    /// Each request with the indices of its folders in `folders`, outermost first
+    pub requests: Vec<(RequestData, Vec<usize>)>,
+}
+
+impl RunPlan {
+    /// Sends the request with the auth, variables and headers of its folders.
+    /// Variables changed by the scripts are written back to the folders and the collection
+    pub fn run(&mut self, client: &Client, index: usize, collection_data: &mut CollectionData, console: &mut Vec<String>) -> anyhow::Result<Outcome> {
+        let (request_data, path) = &self.requests[index];
+        let mut scoped = collection_data.scoped(path.iter().map(|&i| &self.folders[i]));
+        let result = engine::run(client, request_data, &mut scoped, console);
+
+        // Parent folders always come before their children, so these are in order
+        let folders = self.folders.iter_mut().enumerate()
+            .filter(|(i, _)| path.contains(i))
+            .map(|(_, folder)| folder);
+        collection_data.unscope(folders, scoped);
+        result
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunStatus {
+    Pending,
+    Running,
+    Passed,
+    Failed,
+    Error(String),
+    Skipped,
+}
+
+#[derive(Debug, Clone)]
+pub struct RunEntry {
+    pub name: String,
+    pub status: RunStatus,
+    pub response_status: Option<u16>,
+    pub duration: Option<Duration>,
+    pub test_results: Vec<AssertionResult>,
+}
+
+#[derive(Default)]
+struct RunState {
+    entries: Vec<RunEntry>,
+    cancelled: bool,
+    finished_after: Option<Duration>,
+    /// What the scripts changed, once the run is finished
+    changes: Option<RunChanges>,
+}
+
+/// The collection and folder data at the start and the end of a run
+pub struct RunChanges {
+    before: (CollectionData, Vec<FolderData>),
+    after: (CollectionData, Vec<FolderData>),
+}
+
+impl RunChanges {
+    /// Writes back the variables the scripts changed, keeping everything the user edited during the run.
+    /// The folders are in the order of [`RunPlan::folders`], `None` if one was removed in the meantime
+    pub fn write_back<'a>(&self, collection_data: &mut CollectionData, folders: impl IntoIterator<Item = Option<&'a mut FolderData>>) {
+        let (before, after) = (&self.before.0, &self.after.0);
+        merge_pairs(&mut collection_data.variables, &before.variables, &after.variables);
+        for environment in &mut collection_data.environments {
+            let variables = |data: &'_ CollectionData| -> Vec<(String, String)> {
+                data.environments.iter()
+                    .find(|e| e.name == environment.name)
+                    .map(|e| e.variables.clone())
+                    .unwrap_or_default()
+            };
+            let (before, after) = (variables(before), variables(after));
+            merge_pairs(&mut environment.variables, &before, &after);
+        }
+        for ((folder, before), after) in folders.into_iter().zip(&self.before.1).zip(&self.after.1) {
+            if let Some(folder) = folder {
+                merge_pairs(&mut folder.variables, &before.variables, &after.variables);
+            }
+        }
+    }
+}
+
+/// Applies the difference between `before` and `after` to the pairs
+fn merge_pairs(pairs: &mut Vec<(String, String)>, before: &[(String, String)], after: &[(String, String)]) {
+    let before: BTreeMap<&str, &str> = before.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    let after: BTreeMap<&str, &str> = after.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    pairs.retain(|(key, _)| !before.contains_key(key.as_str()) || after.contains_key(key.as_str()));
+    for (key, value) in after {
+        if before.get(key) != Some(&value) {
+            set_pair(pairs, key, value, |a, b| a == b);
+        }
+    }
+}
+
+/// Sends every request of a collection one after another on a background thread
+pub struct CollectionRun {
+    state: Arc<Mutex<RunState>>,
+}
+
+impl CollectionRun {
+    pub fn start(mut plan: RunPlan, mut collection_data: CollectionData, stop_on_failure: bool, ctx: egui::Context) -> Self {
+        let entries = plan.requests.iter().map(|(r, _)| RunEntry {
+            name: r.name.clone(),
+            status: RunStatus::Pending,
+            response_status: None,
+            duration: None,
+            test_results: vec![],
+        }).collect();
+        let state = Arc::new(Mutex::new(RunState { entries, ..Default::default() }));
+        let before = (collection_data.clone(), plan.folders.clone());
+
+        let thread_state = Arc::clone(&state);
+        std::thread::spawn(move || {
+            let start = Instant::now();
+            let client = engine::client();
+            let mut failed = false;
+
+            for i in 0..plan.requests.len() {
+                {
+                    let mut state = thread_state.lock().unwrap();
+                    if state.cancelled || (failed && stop_on_failure) {
+                        state.entries[i].status = RunStatus::Skipped;
+                        continue;
+                    }
+                    state.entries[i].status = RunStatus::Running;
+                }
+                ctx.request_repaint();
+
+                let result = client.as_ref()
+                    .map_err(|e| anyhow::anyhow!("{:#}", e))
+                    .and_then(|client| plan.run(client, i, &mut collection_data, &mut vec![]));
+
+                let mut state = thread_state.lock().unwrap();
+                let entry = &mut state.entries[i];
+                match result {
+                    Ok(outcome) => {
+                        entry.status = if outcome.passed() { RunStatus::Passed } else { RunStatus::Failed };
+                        entry.response_status = Some(outcome.response.status);
+                        entry.duration = Some(outcome.response.duration);
+                        entry.test_results = outcome.test_results;
+                    },
+                    Err(e) => {
+                        entry.status = RunStatus::Error(format!("{:#}", e));
+                    },
+                }
+                failed |= entry.status != RunStatus::Passed;
+                drop(state);
+                ctx.request_repaint();
+            }
+
+            let mut state = thread_state.lock().unwrap();
+            state.finished_after = Some(start.elapsed());
+            state.changes = Some(RunChanges { before, after: (collection_data, plan.folders) });
+            ctx.request_repaint();
+        });
+
+        Self { state }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state.lock().unwrap().finished_after.is_some()
+    }
+
+    pub fn cancel(&self) {
+        self.state.lock().unwrap().cancelled = true;
+    }
+
+    /// Returns what the scripts changed once the run is finished. Only returns it once
+    pub fn take_changes(&self) -> Option<RunChanges> {
+        self.state.lock().unwrap().changes.take()
+    }
+
+    pub fn render(&self, ui: &mut Ui) {
+        let state = self.state.lock().unwrap();
+
+        let count = |status: &dyn Fn(&RunStatus) -> bool| state.entries.iter().filter(|e| status(&e.status)).count();
+        let passed = count(&|s| s == &RunStatus::Passed);
+        let failed = count(&|s| matches!(s, RunStatus::Failed | RunStatus::Error(_)));
+        let skipped = count(&|s| s == &RunStatus::Skipped);
+        let done = passed + failed + skipped;
+
+        match state.finished_after {
+            Some(duration) => {
+                let color = if failed == 0 { Color32::GREEN } else { Color32::RED };
+                ui.colored_label(color, RichText::new(format!(
+                    "{} passed, {} failed, {} skipped in {} ms",
+                    passed, failed, skipped, duration.as_millis()
+                )).strong());
+            },
+            None => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(format!("Running {}/{}", done, state.entries.len()));
+                });
+            },
+        }
+        ui.add(egui::ProgressBar::new(done as f32 / state.entries.len().max(1) as f32));
+        ui.add_space(5.);
+
+        TableBuilder::new(ui)
+            .striped(true)
+            .column(Column::initial(160.).resizable(true))
+            .column(Column::initial(80.))
+            .column(Column::initial(60.))
+            .column(Column::initial(70.))
+            .column(Column::remainder())
+            .header(20., |mut header| {
+                header.col(|ui| { ui.strong("Request"); });
+                header.col(|ui| { ui.strong("Result"); });
+                header.col(|ui| { ui.strong("Status"); });
+                header.col(|ui| { ui.strong("Time"); });
+                header.col(|ui| { ui.strong("Tests"); });
+            })
+            .body(|mut body| {
+                for entry in &state.entries {
+                    body.row(20., |mut row| {
+                        row.col(|ui| { ui.label(&entry.name); });
+                        row.col(|ui| {
+                            match &entry.status {
+                                RunStatus::Pending => ui.weak("Pending"),
+                                RunStatus::Running => ui.spinner(),
+                                RunStatus::Passed => ui.colored_label(Color32::GREEN, "Passed"),
+                                RunStatus::Failed => ui.colored_label(Color32::RED, "Failed"),
+                                RunStatus::Error(e) => ui.colored_label(Color32::RED, "Error").on_hover_text(e),
+                                RunStatus::Skipped => ui.weak("Skipped"),
+                            };
+                        });
+                        row.col(|ui| {
+                            if let Some(status) = entry.response_status {
+                                ui.label(status.to_string());
+                            }
+                        });
+                        row.col(|ui| {
+                            if let Some(duration) = entry.duration {
+                                ui.label(format!("{} ms", duration.as_millis()));
+                            }
+                        });
+                        row.col(|ui| {
+                            if entry.test_results.is_empty() {
+                                return;
+                            }
+                            let passed = entry.test_results.iter().filter(|r| r.passed).count();
+                            let failures = entry.test_results.iter()
+                                .filter(|r| !r.passed)
+                                .map(|r| r.message.as_str())
+                                .collect::<Vec<_>>()
+                                .join("\n");
+                            let label = ui.label(format!("{}/{} passed", passed, entry.test_results.len()));
+                            if !failures.is_empty() {
+                                label.on_hover_text(failures);
+                            }
+                        });
+                    });
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use crate::collection::Environment;
+    use crate::request::assertions::Assertion;
+    use crate::test_support::{self, stand_in};
+
+    use super::*;
+
+    fn pair(key: &str, value: &str) -> (String, String) {
+        (key.to_owned(), value.to_owned())
+    }
+
+    fn plan(address: &str, count: usize) -> RunPlan {
+        let requests = (0..count).map(|i| {
+            let request_data = RequestData {
+                name: format!("Request {}", i),
+                url_string: format!("http://{}/{}", address, i),
+                assertions: vec![Assertion::StatusEquals { status: 200 }],
+                pre_request_script: format!(r#"variables.last = "{}";"#, i),
+                ..Default::default()
+            };
+            (request_data, vec![])
+        }).collect();
+        RunPlan { folders: vec![], requests }
+    }
+
+    fn finish(run: &CollectionRun) -> Vec<RunStatus> {
+        while !run.is_finished() {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        run.state.lock().unwrap().entries.iter().map(|e| e.status.clone()).collect()
+    }
+
+    #[test]
+    fn stops_on_failure_if_asked_to() {
+        let (address, _) = stand_in(|_| test_support::status(500));
+
+        let run = CollectionRun::start(plan(&address, 3), CollectionData::default(), true, egui::Context::default());
+        assert_eq!(finish(&run), vec![RunStatus::Failed, RunStatus::Skipped, RunStatus::Skipped]);
+
+        let run = CollectionRun::start(plan(&address, 3), CollectionData::default(), false, egui::Context::default());
+        assert_eq!(finish(&run), vec![RunStatus::Failed, RunStatus::Failed, RunStatus::Failed]);
+        let entry = &run.state.lock().unwrap().entries[2];
+        assert_eq!(entry.response_status, Some(500));
+        assert_eq!(entry.test_results[0].message, "Status is 500, expected 200");
+    }
+
+    #[test]
+    fn cancelling_skips_the_remaining_requests() {
+        let (open, gate) = mpsc::channel();
+        // Each answer waits until the gate is opened
+        let (address, _) = stand_in(move |_| {
+            let _ = gate.recv();
+            test_support::status(200)
+        });
+
+        let run = CollectionRun::start(plan(&address, 3), CollectionData::default(), false, egui::Context::default());
+        while run.state.lock().unwrap().entries[0].status != RunStatus::Running {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        run.cancel();
+        open.send(()).unwrap();
+
+        assert_eq!(finish(&run), vec![RunStatus::Passed, RunStatus::Skipped, RunStatus::Skipped]);
+        // The variables of the request that ran are still written back
+        let changes = run.take_changes().unwrap();
+        assert!(run.take_changes().is_none());
+        let mut data = CollectionData::default();
+        changes.write_back(&mut data, []);
+        assert_eq!(data.variables, vec![pair("last", "0")]);
+    }
+
+    #[test]
+    fn only_the_changes_of_the_scripts_are_written_back() {
+        let environment = |variables| Environment { name: String::from("Dev"), variables };
+        let before = CollectionData {
+            variables: vec![pair("a", "1"), pair("b", "2"), pair("c", "3")],
+            environments: vec![environment(vec![pair("token", "old")])],
+            ..Default::default()
+        };
+        // The scripts changed b, removed c, added d and set a new token
+        let after = CollectionData {
+            variables: vec![pair("a", "1"), pair("b", "20"), pair("d", "4")],
+            environments: vec![environment(vec![pair("token", "new")])],
+            ..Default::default()
+        };
+        let folder = |variables| FolderData { variables, ..Default::default() };
+        let changes = RunChanges {
+            before: (before, vec![folder(vec![pair("page", "1")]), folder(vec![])]),
+            after: (after, vec![folder(vec![pair("page", "2")]), folder(vec![pair("x", "y")])]),
+        };
+
+        // Meanwhile the user changed a, added e and a second environment, and removed the second folder
+        let mut current = CollectionData {
+            variables: vec![pair("a", "100"), pair("b", "2"), pair("c", "3"), pair("e", "5")],
+            environments: vec![environment(vec![pair("token", "old")]), Environment { name: String::from("Prod"), variables: vec![pair("token", "prod")] }],
+            ..Default::default()
+        };
+        let mut first = folder(vec![pair("page", "1"), pair("size", "10")]);
+        changes.write_back(&mut current, [Some(&mut first), None]);
+
+        assert_eq!(current.variables, vec![pair("a", "100"), pair("b", "20"), pair("e", "5"), pair("d", "4")]);
+        assert_eq!(current.environments[0].variables, vec![pair("token", "new")]);
+        assert_eq!(current.environments[1].variables, vec![pair("token", "prod")]);
+        assert_eq!(first.variables, vec![pair("page", "2"), pair("size", "10")]);
+    }
+}
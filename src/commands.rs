@@ -0,0 +1,411 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Actions that can be run from the command palette or with a keyboard shortcut
+
+use std::collections::BTreeMap;
+
+use egui::{Context, Event, Id, Key, KeyboardShortcut, Layout, Align, Modifiers, ScrollArea, TextEdit, Align2};
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum Command {
+    /// Was called `CommandPalette` in stored keybindings
+    #[serde(alias = "CommandPalette")]
+    OpenPalette,
+    Send,
+    Save,
+    Undo,
+    Redo,
+    NewRequest,
+    DuplicateRequest,
+    CloseTab,
+    SwitchEnvironment,
+    CompareResponses,
+    Find,
+    Search,
+    Import,
+    OpenWorkspace,
+    SaveWorkspace,
+    KeyboardShortcuts,
+}
+
+impl Command {
+    pub const ALL: [Command; 16] = [
+        Command::OpenPalette,
+        Command::Send,
+        Command::Save,
+        Command::Undo,
+        Command::Redo,
+        Command::NewRequest,
+        Command::DuplicateRequest,
+        Command::CloseTab,
+        Command::SwitchEnvironment,
+        Command::CompareResponses,
+        Command::Find,
+        Command::Search,
+        Command::Import,
+        Command::OpenWorkspace,
+        Command::SaveWorkspace,
+        Command::KeyboardShortcuts,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::OpenPalette => "Command Palette",
+            Command::Send => "Send Request",
+            Command::Save => "Save Request",
+            Command::Undo => "Undo",
+            Command::Redo => "Redo",
+            Command::NewRequest => "New Request",
+            Command::DuplicateRequest => "Duplicate Request",
+            Command::CloseTab => "Close Tab",
+            Command::SwitchEnvironment => "Switch Environment",
+            Command::CompareResponses => "Compare Responses",
+            Command::Find => "Find in Body",
+            Command::Search => "Search Collections",
+            Command::Import => "Import Collection",
+            Command::OpenWorkspace => "Open Workspace",
+            Command::SaveWorkspace => "Save Workspace",
+            Command::KeyboardShortcuts => "Keyboard Shortcuts",
+        }
+    }
+
+    fn default_shortcuts(self) -> Vec<Shortcut> {
+        let command = |key| Shortcut { modifiers: Modifiers::COMMAND, key };
+        let command_shift = |key| Shortcut { modifiers: Modifiers::COMMAND | Modifiers::SHIFT, key };
+        match self {
+            Command::OpenPalette => vec![command(Key::K), command_shift(Key::P)],
+            Command::Send => vec![command(Key::Enter)],
+            Command::Save => vec![command(Key::S)],
+            Command::Undo => vec![command(Key::Z)],
+            Command::Redo => vec![command_shift(Key::Z), command(Key::Y)],
+            Command::NewRequest => vec![command(Key::N)],
+            Command::DuplicateRequest => vec![command(Key::D)],
+            Command::CloseTab => vec![command(Key::W)],
+            Command::SwitchEnvironment => vec![command(Key::E)],
+            Command::CompareResponses => vec![],
+            Command::Find => vec![command(Key::F)],
+            Command::Search => vec![command_shift(Key::F)],
+            Command::Import => vec![command(Key::I)],
+            Command::OpenWorkspace => vec![command(Key::O)],
+            Command::SaveWorkspace => vec![command_shift(Key::S)],
+            Command::KeyboardShortcuts => vec![],
+        }
+    }
+}
+
+/// A key with modifiers. Unlike [`KeyboardShortcut`] it can be persisted
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Shortcut {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl Shortcut {
+    fn to_egui(self) -> KeyboardShortcut {
+        KeyboardShortcut::new(self.modifiers, self.key)
+    }
+
+    pub fn format(self, ctx: &Context) -> String {
+        ctx.format_shortcut(&self.to_egui())
+    }
+
+    /// Shortcuts need Ctrl, Cmd or Alt, so they don't take keys away from typing. Function keys work alone
+    fn is_allowed(self) -> bool {
+        let modifiers = self.modifiers;
+        let function_key = matches!(self.key,
+            Key::F1 | Key::F2 | Key::F3 | Key::F4 | Key::F5 | Key::F6 | Key::F7 | Key::F8 | Key::F9 | Key::F10
+            | Key::F11 | Key::F12 | Key::F13 | Key::F14 | Key::F15 | Key::F16 | Key::F17 | Key::F18 | Key::F19 | Key::F20);
+        modifiers.ctrl || modifiers.command || modifiers.mac_cmd || modifiers.alt || function_key
+    }
+}
+
+/// The shortcuts of the commands. Only the ones the user changed are stored,
+/// so new commands get their default shortcuts
+#[derive(Serialize, Deserialize, Default)]
+pub struct Keybindings {
+    changed: BTreeMap<Command, Vec<Shortcut>>,
+}
+
+impl Keybindings {
+    pub fn shortcuts(&self, command: Command) -> Vec<Shortcut> {
+        self.changed.get(&command).cloned().unwrap_or_else(|| command.default_shortcuts())
+    }
+
+    /// The commands whose shortcuts were pressed this frame. Consumes the key presses.
+    /// Commands that aren't `enabled` leave their keys to the widgets, like undo in a text field
+    pub fn pressed(&self, ctx: &Context, enabled: impl Fn(Command) -> bool) -> Vec<Command> {
+        Command::ALL.into_iter()
+            .filter(|command| enabled(*command))
+            .filter(|command| {
+                self.shortcuts(*command).iter().any(|s| ctx.input_mut(|i| i.consume_shortcut(&s.to_egui())))
+            })
+            .collect()
+    }
+
+    /// The shortcut the command is shown with
+    pub fn label(&self, ctx: &Context, command: Command) -> String {
+        self.shortcuts(command).first().map(|s| s.format(ctx)).unwrap_or_default()
+    }
+
+    /// Takes the shortcut away from any other command
+    fn bind(&mut self, command: Command, shortcut: Shortcut) {
+        for other in Command::ALL {
+            let mut shortcuts = self.shortcuts(other);
+            if other != command && shortcuts.contains(&shortcut) {
+                shortcuts.retain(|s| s != &shortcut);
+                self.changed.insert(other, shortcuts);
+            }
+        }
+        let mut shortcuts = self.shortcuts(command);
+        if !shortcuts.contains(&shortcut) {
+            shortcuts.push(shortcut);
+        }
+        self.changed.insert(command, shortcuts);
+    }
+
+    fn unbind(&mut self, command: Command, shortcut: Shortcut) {
+        let mut shortcuts = self.shortcuts(command);
+        shortcuts.retain(|s| s != &shortcut);
+        self.changed.insert(command, shortcuts);
+    }
+}
+
+/// The window to change the shortcuts
+#[derive(Default)]
+pub struct KeybindingsWindow {
+    pub open: bool,
+    /// The command that gets the next key that is pressed
+    recording: Option<Command>,
+}
+
+impl KeybindingsWindow {
+    /// True while a new shortcut is being recorded, so the shortcuts should not run
+    pub fn is_recording(&self) -> bool {
+        self.open && self.recording.is_some()
+    }
+
+    pub fn render(&mut self, ctx: &Context, keybindings: &mut Keybindings) {
+        if let Some(command) = self.recording {
+            let pressed = ctx.input(|i| i.events.iter().find_map(|event| match event {
+                Event::Key { key, pressed: true, modifiers, .. } => Some((*key, *modifiers)),
+                _ => None,
+            }));
+            match pressed {
+                Some((Key::Escape, modifiers)) if modifiers.is_none() => self.recording = None,
+                Some((key, modifiers)) if (Shortcut { modifiers, key }).is_allowed() => {
+                    keybindings.bind(command, Shortcut { modifiers, key });
+                    self.recording = None;
+                },
+                // Keys without a modifier are left for typing, keep waiting for a shortcut
+                Some(_) | None => {},
+            }
+        }
+
+        let mut open = self.open;
+        egui::Window::new("Keyboard Shortcuts")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("keybindings").num_columns(2).striped(true).show(ui, |ui| {
+                    for command in Command::ALL {
+                        ui.label(command.name());
+                        ui.horizontal(|ui| {
+                            for shortcut in keybindings.shortcuts(command) {
+                                if ui.button(shortcut.format(ctx)).on_hover_text("Click to remove").clicked() {
+                                    keybindings.unbind(command, shortcut);
+                                }
+                            }
+                            let recording = self.recording == Some(command);
+                            let text = if recording { "Press a shortcut…" } else { "+" };
+                            let hint = "Add a shortcut with Ctrl, Cmd or Alt, or a function key";
+                            if ui.selectable_label(recording, text).on_hover_text(hint).clicked() {
+                                self.recording = if recording { None } else { Some(command) };
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+                ui.add_space(5.);
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    if ui.button("Reset to Defaults").clicked() {
+                        *keybindings = Keybindings::default();
+                    }
+                });
+            });
+        self.open = open;
+        if !open {
+            self.recording = None;
+        }
+    }
+}
+
+/// Something the command palette can do
+#[derive(Clone, PartialEq, Eq)]
+pub enum PaletteEntry {
+    Command(Command),
+    /// Selects the environment of the collection, or no environment
+    Environment { collection: Uuid, name: Option<String>, label: String },
+}
+
+/// Lists commands and environments, filtered by what is typed
+#[derive(Default)]
+pub struct CommandPalette {
+    open: bool,
+    query: String,
+    highlighted: usize,
+}
+
+impl CommandPalette {
+    /// Opens the palette with the query already typed in
+    pub fn open(&mut self, ctx: &Context, query: &str) {
+        self.open = true;
+        self.query = query.to_owned();
+        self.highlighted = 0;
+        ctx.memory_mut(|m| m.request_focus(Id::new("command_palette")));
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Returns the entry that was chosen
+    pub fn render(&mut self, ctx: &Context, entries: &[PaletteEntry], keybindings: &Keybindings) -> Option<PaletteEntry> {
+        if !self.open {
+            return None;
+        }
+        let query = self.query.to_lowercase();
+        let matching: Vec<&PaletteEntry> = entries.iter()
+            .filter(|entry| query.split_whitespace().all(|word| entry_label(entry).to_lowercase().contains(word)))
+            .collect();
+        self.highlighted = self.highlighted.min(matching.len().saturating_sub(1));
+
+        let (up, down, enter, escape) = ctx.input_mut(|i| (
+            i.consume_key(Modifiers::NONE, Key::ArrowUp),
+            i.consume_key(Modifiers::NONE, Key::ArrowDown),
+            i.consume_key(Modifiers::NONE, Key::Enter),
+            i.consume_key(Modifiers::NONE, Key::Escape),
+        ));
+        if up {
+            self.highlighted = self.highlighted.saturating_sub(1);
+        }
+        if down {
+            self.highlighted = (self.highlighted + 1).min(matching.len().saturating_sub(1));
+        }
+        let mut chosen = enter.then(|| matching.get(self.highlighted).map(|e| (*e).clone())).flatten();
+
+        egui::Window::new("Command Palette")
+            .title_bar(false)
+            .resizable(false)
+            .fixed_size([400., 0.])
+            .anchor(Align2::CENTER_TOP, [0., 60.])
+            .show(ctx, |ui| {
+                let response = ui.add(TextEdit::singleline(&mut self.query)
+                    .id(Id::new("command_palette"))
+                    .hint_text("Type a command")
+                    .desired_width(f32::INFINITY));
+                if response.changed() {
+                    self.highlighted = 0;
+                }
+                ui.separator();
+                ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+                    if matching.is_empty() {
+                        ui.weak("No matching commands");
+                    }
+                    for (i, entry) in matching.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            let response = ui.selectable_label(i == self.highlighted, entry_label(entry));
+                            if response.clicked() {
+                                chosen = Some((*entry).clone());
+                            }
+                            if (up || down) && i == self.highlighted {
+                                response.scroll_to_me(None);
+                            }
+                            if let PaletteEntry::Command(command) = entry {
+                                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                                    ui.weak(keybindings.label(ctx, *command));
+                                });
+                            }
+                        });
+                    }
+                });
+            });
+
+        if chosen.is_some() || escape {
+            self.open = false;
+        }
+        chosen
+    }
+}
+
+fn entry_label(entry: &PaletteEntry) -> &str {
+    match entry {
+        PaletteEntry::Command(command) => command.name(),
+        PaletteEntry::Environment { label, .. } => label,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_a_shortcut_takes_it_from_other_commands() {
+        let mut keybindings = Keybindings::default();
+        let save = Command::Save.default_shortcuts()[0];
+
+        keybindings.bind(Command::Send, save);
+
+        assert!(keybindings.shortcuts(Command::Send).contains(&save));
+        assert!(keybindings.shortcuts(Command::Save).is_empty());
+        // Untouched commands keep their defaults
+        assert_eq!(keybindings.shortcuts(Command::CloseTab), Command::CloseTab.default_shortcuts());
+    }
+
+    #[test]
+    fn shortcuts_need_a_modifier_or_a_function_key() {
+        let shortcut = |modifiers, key| Shortcut { modifiers, key };
+        assert!(shortcut(Modifiers::COMMAND, Key::K).is_allowed());
+        assert!(shortcut(Modifiers::ALT | Modifiers::SHIFT, Key::R).is_allowed());
+        assert!(shortcut(Modifiers::NONE, Key::F5).is_allowed());
+        assert!(!shortcut(Modifiers::NONE, Key::A).is_allowed());
+        assert!(!shortcut(Modifiers::SHIFT, Key::Enter).is_allowed());
+        assert!(Command::ALL.iter().flat_map(|c| c.default_shortcuts()).all(Shortcut::is_allowed));
+    }
+
+    #[test]
+    fn stored_palette_shortcuts_keep_working() {
+        let loaded: Keybindings = serde_json::from_str(r#"{"changed": {"CommandPalette": []}}"#).unwrap();
+        assert!(loaded.shortcuts(Command::OpenPalette).is_empty());
+    }
+
+    #[test]
+    fn changed_shortcuts_survive_a_round_trip() {
+        let mut keybindings = Keybindings::default();
+        let shortcut = Shortcut { modifiers: Modifiers::ALT, key: Key::R };
+        keybindings.bind(Command::Send, shortcut);
+        keybindings.unbind(Command::CloseTab, Command::CloseTab.default_shortcuts()[0]);
+
+        let json = serde_json::to_string(&keybindings).unwrap();
+        let loaded: Keybindings = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(loaded.shortcuts(Command::Send).last(), Some(&shortcut));
+        assert!(loaded.shortcuts(Command::CloseTab).is_empty());
+    }
+
+    #[test]
+    fn disabled_commands_leave_their_keys_to_the_widgets() {
+        let keybindings = Keybindings::default();
+        let ctx = Context::default();
+        let undo = Event::Key { key: Key::Z, pressed: true, repeat: false, modifiers: Modifiers::COMMAND };
+        ctx.begin_frame(egui::RawInput { events: vec![undo], ..Default::default() });
+
+        assert!(keybindings.pressed(&ctx, |command| command != Command::Undo).is_empty());
+        assert_eq!(ctx.input(|i| i.events.len()), 1);
+        assert_eq!(keybindings.pressed(&ctx, |_| true), vec![Command::Undo]);
+        assert!(ctx.input(|i| i.events.is_empty()));
+    }
+}
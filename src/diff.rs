@@ -0,0 +1,448 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Compares two responses: their bodies line by line, their JSON by structure and their headers
+
+use std::collections::{BTreeMap, HashMap};
+
+use egui::{Color32, Context, RichText, ScrollArea, Ui};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::json_path::{child_path, index_path};
+use crate::request::{Request, RecordedResponse};
+
+/// Bigger differences are shown as replacing everything, so the diff stays fast
+const MAX_EDITS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Same,
+    Removed,
+    Added,
+}
+
+/// The changes that turn the old text into the new one, line by line
+pub fn lines<'a>(old: &'a str, new: &'a str) -> Vec<(Change, &'a str)> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (old_middle, new_middle) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut changes: Vec<(Change, &str)> = old[..prefix].iter().map(|line| (Change::Same, *line)).collect();
+    match myers(old_middle, new_middle) {
+        Some(middle) => changes.extend(middle),
+        None => {
+            changes.extend(old_middle.iter().map(|line| (Change::Removed, *line)));
+            changes.extend(new_middle.iter().map(|line| (Change::Added, *line)));
+        },
+    }
+    changes.extend(old[old.len() - suffix..].iter().map(|line| (Change::Same, *line)));
+    changes
+}
+
+/// The shortest edit script of Myers' algorithm. Returns `None` if it needs more than [`MAX_EDITS`] edits
+fn myers<'a>(old: &[&'a str], new: &[&'a str]) -> Option<Vec<(Change, &'a str)>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m) as usize;
+    // The furthest x on every diagonal k, at index k + offset
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    // The diagonals -(d + 1)..=(d + 1) of v before each step d, for going back afterwards
+    let mut trace: Vec<Vec<isize>> = vec![];
+
+    'search: for d in 0..=max.min(MAX_EDITS) as isize {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) { v[i + 1] } else { v[i - 1] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+        if d as usize == max.min(MAX_EDITS) {
+            return None;
+        }
+    }
+
+    let mut changes = vec![];
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) { k + 1 } else { k - 1 };
+        let previous_x = at(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            changes.push((Change::Same, old[x as usize]));
+        }
+        if d > 0 {
+            match x == previous_x {
+                true => changes.push((Change::Added, new[previous_y as usize])),
+                false => changes.push((Change::Removed, old[previous_x as usize])),
+            }
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    changes.reverse();
+    Some(changes)
+}
+
+/// Something that is different between the two responses. `None` means it is missing on that side
+#[derive(Debug, PartialEq, Eq)]
+pub struct Difference {
+    pub path: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// The values that were added, removed or changed. The order of object keys is ignored
+pub fn json(old: &Value, new: &Value) -> Vec<Difference> {
+    let mut differences = vec![];
+    compare_json(String::from("$"), Some(old), Some(new), &mut differences);
+    differences
+}
+
+fn compare_json(path: String, old: Option<&Value>, new: Option<&Value>, differences: &mut Vec<Difference>) {
+    match (old, new) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                compare_json(child_path(&path, key), old.get(key), new.get(key), differences);
+            }
+        },
+        (Some(Value::Array(old)), Some(Value::Array(new))) => {
+            for i in 0..old.len().max(new.len()) {
+                compare_json(index_path(&path, i), old.get(i), new.get(i), differences);
+            }
+        },
+        (old, new) if old != new => differences.push(Difference {
+            path,
+            old: old.map(Value::to_string),
+            new: new.map(Value::to_string),
+        }),
+        _ => {},
+    }
+}
+
+/// Headers that differ, by name ignoring case. Headers that are sent more than once are joined
+pub fn headers(old: &[(String, String)], new: &[(String, String)]) -> Vec<Difference> {
+    let collect = |headers: &[(String, String)]| {
+        let mut map: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+        for (key, value) in headers {
+            map.entry(key.to_lowercase()).or_default().push(value);
+        }
+        map.into_iter().map(|(key, values)| (key, values.join(", "))).collect::<BTreeMap<_, _>>()
+    };
+    let (mut old, mut new) = (collect(old), collect(new));
+    let mut names: Vec<String> = old.keys().chain(new.keys()).cloned().collect();
+    names.sort();
+    names.dedup();
+    names.into_iter()
+        .filter_map(|name| {
+            let (old, new) = (old.remove(&name), new.remove(&name));
+            (old != new).then_some(Difference { path: name, old, new })
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DiffMode {
+    Body,
+    Json,
+    Headers,
+}
+
+/// A response of an open request, by the run it came from
+type Selection = (Uuid, usize);
+
+/// One side of a row in the side by side view
+type Side = Option<(Change, String)>;
+
+/// The old line on the left and the new one on the right
+type Row = (Side, Side);
+
+/// The differences of the selected responses, so they aren't computed every frame
+struct Computed {
+    selection: (Selection, Selection),
+    /// Rows of the side by side view
+    rows: Vec<Row>,
+    /// `None` if one of the bodies isn't JSON
+    json: Option<Vec<Difference>>,
+    headers: Vec<Difference>,
+}
+
+/// The window to compare two responses of the open requests
+pub struct DiffWindow {
+    pub open: bool,
+    left: Option<Selection>,
+    right: Option<Selection>,
+    mode: DiffMode,
+    computed: Option<Computed>,
+}
+
+impl Default for DiffWindow {
+    fn default() -> Self {
+        Self {
+            open: false,
+            left: None,
+            right: None,
+            mode: DiffMode::Body,
+            computed: None,
+        }
+    }
+}
+
+impl DiffWindow {
+    /// Opens the window with the last two responses of the request
+    pub fn compare_latest(&mut self, request: &Request) {
+        let responses = request.responses();
+        let mut latest = responses.iter().rev().map(|r| (request.uuid, r.run));
+        self.right = latest.next();
+        self.left = latest.next().or(self.right);
+        self.open = true;
+    }
+
+    pub fn render(&mut self, ctx: &Context, requests: &HashMap<Uuid, Request>) {
+        if !self.open {
+            return;
+        }
+        let mut all: Vec<(Selection, String, &RecordedResponse)> = requests.values()
+            .flat_map(|request| request.responses().iter().map(move |r| {
+                ((request.uuid, r.run), format!("{} · {}", request.name(), r.label), r)
+            }))
+            .collect();
+        // Run 10 comes after run 9, not after run 1
+        all.sort_by_cached_key(|((uuid, run), ..)| (requests[uuid].name(), *uuid, *run));
+        let find = |selection: Option<Selection>| selection.and_then(|s| all.iter().find(|(other, ..)| other == &s));
+
+        let mut open = self.open;
+        egui::Window::new("Compare Responses")
+            .open(&mut open)
+            .default_size([800., 500.])
+            .show(ctx, |ui| {
+                if all.is_empty() {
+                    ui.weak("Send a request to compare its responses");
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    select_response(ui, "diff_left", &mut self.left, &all);
+                    ui.label("→");
+                    select_response(ui, "diff_right", &mut self.right, &all);
+                });
+                let (Some(left), Some(right)) = (find(self.left), find(self.right)) else {
+                    ui.weak("Choose two responses");
+                    return;
+                };
+                let selection = (left.0, right.0);
+                if self.computed.as_ref().map_or(true, |c| c.selection != selection) {
+                    self.computed = Some(compute(selection, left.2, right.2));
+                }
+                let Some(computed) = &self.computed else {
+                    return;
+                };
+
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.mode, DiffMode::Body, "Body");
+                    ui.add_enabled_ui(computed.json.is_some(), |ui| {
+                        ui.selectable_value(&mut self.mode, DiffMode::Json, "JSON");
+                    });
+                    ui.selectable_value(&mut self.mode, DiffMode::Headers, format!("Headers ({})", computed.headers.len()));
+                    ui.separator();
+                    for response in [&left.2.response, &right.2.response] {
+                        ui.weak(format!("{} · {} ms", response.status, response.duration.as_millis()));
+                    }
+                });
+                ui.separator();
+
+                match self.mode {
+                    DiffMode::Body => render_rows(ui, &computed.rows),
+                    DiffMode::Json => match &computed.json {
+                        Some(differences) => render_differences(ui, differences, "The JSON is the same"),
+                        None => {
+                            ui.weak("One of the bodies is not JSON");
+                        },
+                    },
+                    DiffMode::Headers => render_differences(ui, &computed.headers, "The headers are the same"),
+                }
+            });
+        self.open = open;
+    }
+}
+
+fn compute(selection: (Selection, Selection), left: &RecordedResponse, right: &RecordedResponse) -> Computed {
+    let (old_text, new_text) = (left.response.text(), right.response.text());
+    let json = match (left.response.json(), right.response.json()) {
+        (Ok(old), Ok(new)) => Some(json(&old, &new)),
+        _ => None,
+    };
+    Computed {
+        selection,
+        rows: side_by_side(&lines(&old_text, &new_text)),
+        json,
+        headers: headers(&left.response.headers, &right.response.headers),
+    }
+}
+
+/// Puts removed and added lines next to each other
+fn side_by_side(changes: &[(Change, &str)]) -> Vec<Row> {
+    let mut rows = vec![];
+    let mut i = 0;
+    while i < changes.len() {
+        if changes[i].0 == Change::Same {
+            let line = changes[i].1.to_owned();
+            rows.push((Some((Change::Same, line.clone())), Some((Change::Same, line))));
+            i += 1;
+            continue;
+        }
+        let removed: Vec<&str> = changes[i..].iter().take_while(|c| c.0 == Change::Removed).map(|c| c.1).collect();
+        i += removed.len();
+        let added: Vec<&str> = changes[i..].iter().take_while(|c| c.0 == Change::Added).map(|c| c.1).collect();
+        i += added.len();
+        for j in 0..removed.len().max(added.len()) {
+            rows.push((
+                removed.get(j).map(|line| (Change::Removed, line.to_string())),
+                added.get(j).map(|line| (Change::Added, line.to_string())),
+            ));
+        }
+    }
+    rows
+}
+
+fn select_response(ui: &mut Ui, id: &str, selected: &mut Option<Selection>, all: &[(Selection, String, &RecordedResponse)]) {
+    let text = selected
+        .and_then(|s| all.iter().find(|(other, ..)| other == &s))
+        .map_or("Choose a response", |(_, label, _)| label.as_str());
+    egui::ComboBox::from_id_source(id)
+        .selected_text(text)
+        .width(300.)
+        .show_ui(ui, |ui| {
+            for (selection, label, _) in all {
+                ui.selectable_value(selected, Some(*selection), label);
+            }
+        });
+}
+
+fn change_color(ui: &Ui, change: Change) -> Color32 {
+    let alpha = if ui.visuals().dark_mode { 40 } else { 60 };
+    match change {
+        Change::Same => Color32::TRANSPARENT,
+        Change::Removed => Color32::from_rgba_unmultiplied(255, 60, 60, alpha),
+        Change::Added => Color32::from_rgba_unmultiplied(60, 200, 60, alpha),
+    }
+}
+
+fn render_rows(ui: &mut Ui, rows: &[Row]) {
+    if rows.iter().all(|(left, _)| matches!(left, Some((Change::Same, _)))) {
+        ui.weak("The bodies are the same");
+    }
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+    ScrollArea::both().auto_shrink([false; 2]).show_rows(ui, row_height, rows.len(), |ui, range| {
+        for (left, right) in &rows[range] {
+            ui.columns(2, |columns| {
+                for (ui, side) in columns.iter_mut().zip([left, right]) {
+                    if let Some((change, line)) = side {
+                        let color = change_color(ui, *change);
+                        ui.label(RichText::new(line).monospace().background_color(color));
+                    }
+                }
+            });
+        }
+    });
+}
+
+fn render_differences(ui: &mut Ui, differences: &[Difference], same: &str) {
+    if differences.is_empty() {
+        ui.weak(same);
+        return;
+    }
+    ScrollArea::both().auto_shrink([false; 2]).show(ui, |ui| {
+        egui::Grid::new("differences").num_columns(3).striped(true).show(ui, |ui| {
+            ui.strong("Path");
+            ui.strong("Before");
+            ui.strong("After");
+            ui.end_row();
+            for difference in differences {
+                ui.monospace(&difference.path);
+                for (value, change) in [(&difference.old, Change::Removed), (&difference.new, Change::Added)] {
+                    match value {
+                        Some(value) => ui.label(RichText::new(value).monospace().background_color(change_color(ui, change))),
+                        None => ui.weak("missing"),
+                    };
+                }
+                ui.end_row();
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn line_diff_keeps_common_lines() {
+        let changes = lines("a\nb\nc\nd", "a\nc\nd\ne");
+        assert_eq!(changes, vec![
+            (Change::Same, "a"),
+            (Change::Removed, "b"),
+            (Change::Same, "c"),
+            (Change::Same, "d"),
+            (Change::Added, "e"),
+        ]);
+        assert!(lines("same\ntext", "same\ntext").iter().all(|(change, _)| *change == Change::Same));
+    }
+
+    #[test]
+    fn line_diff_of_changed_lines() {
+        let changes = lines("x\none\ny", "x\ntwo\ny");
+        assert_eq!(changes, vec![
+            (Change::Same, "x"),
+            (Change::Removed, "one"),
+            (Change::Added, "two"),
+            (Change::Same, "y"),
+        ]);
+        let rows = side_by_side(&changes);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1], (Some((Change::Removed, String::from("one"))), Some((Change::Added, String::from("two")))));
+    }
+
+    #[test]
+    fn json_diff_ignores_key_order() {
+        let old = json!({ "id": 1, "tags": ["a", "b"], "user": { "name": "Ada", "e-mail": "ada@example.com" } });
+        let new = json!({ "user": { "e-mail": "ada@example.com", "name": "Grace" }, "tags": ["a"], "id": 1, "new": true });
+        assert_eq!(json(&old, &new), vec![
+            Difference { path: String::from("$.new"), old: None, new: Some(String::from("true")) },
+            Difference { path: String::from("$.tags[1]"), old: Some(String::from("\"b\"")), new: None },
+            Difference { path: String::from("$.user.name"), old: Some(String::from("\"Ada\"")), new: Some(String::from("\"Grace\"")) },
+        ]);
+        assert!(json(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn header_diff_ignores_case() {
+        let old = vec![(String::from("Content-Type"), String::from("text/plain")), (String::from("ETag"), String::from("1"))];
+        let new = vec![(String::from("content-type"), String::from("text/plain")), (String::from("Age"), String::from("3"))];
+        assert_eq!(headers(&old, &new), vec![
+            Difference { path: String::from("age"), old: None, new: Some(String::from("3")) },
+            Difference { path: String::from("etag"), old: Some(String::from("1")), new: None },
+        ]);
+    }
+}
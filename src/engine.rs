@@ -0,0 +1,586 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Turns the saved [`RequestData`] into the request that actually goes over the wire, sends it
+//! and checks the assertions. Nothing in here depends on egui, so other tools can use it directly:
+//!
+//! ```no_run
+//! use packets::engine::{self, CollectionData, RequestData};
+//!
+//! let request_data = RequestData { url_string: String::from("https://example.com"), ..Default::default() };
+//! let mut collection_data = CollectionData::default();
+//!
+//! let client = engine::client()?;
+//! let outcome = engine::run(&client, &request_data, &mut collection_data, &mut vec![])?;
+//! println!("{} {}", outcome.response.status, outcome.response.text());
+//! # anyhow::Ok(())
+//! ```
+//!
+//! Everything that is derived at send time (like the `Authorization` header) is added here,
+//! so the data the user edits stays untouched.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use reqwest::blocking::{Client, Request};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+
+use crate::request::assertions;
+use crate::script::{self, ScriptContext};
+
+pub use crate::collection::{CollectionData, CollectionFile, Environment, FolderData, FolderFile, RunPlan};
+pub use crate::jwt::JwtAlgorithm;
+pub use crate::request::{RequestData, RequestMethod};
+pub use crate::request::assertions::{Assertion, AssertionResult};
+pub use crate::request::tabs::body_tab::{BodyData, BodyType};
+pub use crate::tabs::auth::{AuthData, AuthType};
+pub use crate::workspace::Workspace;
+
+/// The request after the pre-request scripts ran and the variables were filled in
+pub struct PreparedRequest {
+    pub request_data: RequestData,
+    /// The variables including the changes made by the scripts
+    pub variables: BTreeMap<String, String>,
+}
+
+/// How much of a downloaded body is kept in memory, for the preview and the assertions
+pub const PREVIEW_BYTES: usize = 64 * 1024;
+
+/// Everything we keep of a response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseData {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// The whole body, or only the start of it if it was downloaded into a file
+    pub body: Vec<u8>,
+    /// The size of the whole body
+    pub size: u64,
+    /// Time until the whole body was received
+    pub duration: Duration,
+}
+
+impl ResponseData {
+    /// The first header with this name, ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    /// True if `body` only holds the start of the body
+    pub fn is_truncated(&self) -> bool {
+        self.size > self.body.len() as u64
+    }
+
+    /// The body as text. Invalid UTF-8 is replaced
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.body)
+    }
+
+    pub fn json(&self) -> anyhow::Result<serde_json::Value> {
+        serde_json::from_slice(&self.body).context("The body is not JSON")
+    }
+}
+
+/// A response together with the results of the assertions of the request
+pub struct Outcome {
+    pub response: ResponseData,
+    pub test_results: Vec<AssertionResult>,
+    /// The variables including the changes made by the scripts
+    pub variables: BTreeMap<String, String>,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.test_results.iter().all(|r| r.passed)
+    }
+}
+
+/// Replaces every `{{name}}` with the value of the variable. Unknown variables are kept as they are
+pub fn substitute(text: &str, variables: &BTreeMap<String, String>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + end].trim();
+        result.push_str(&rest[..start]);
+        match variables.get(name) {
+            Some(value) => result.push_str(value),
+            None => result.push_str(&rest[start..start + end + 2]),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+fn substitute_request(request_data: &mut RequestData, variables: &BTreeMap<String, String>) {
+    request_data.url_string = substitute(&request_data.url_string, variables);
+    for (key, value) in request_data.headers.iter_mut() {
+        *key = substitute(key, variables);
+        *value = substitute(value, variables);
+    }
+    if let Some(BodyData::Raw { data }) = request_data.body.get_mut(&request_data.selected_body) {
+        *data = substitute(data, variables);
+    }
+    match request_data.auth.get_mut(&request_data.selected_auth) {
+        Some(AuthData::Basic { username, password }) => {
+            *username = substitute(username, variables);
+            *password = substitute(password, variables);
+        },
+        Some(AuthData::Bearer { token }) => {
+            *token = substitute(token, variables);
+        },
+        Some(AuthData::Jwt { key, claims, .. }) => {
+            *key = substitute(key, variables);
+            *claims = substitute(claims, variables);
+        },
+        _ => {},
+    }
+}
+
+/// Runs the pre-request scripts of the collection and the request, then fills in the variables.
+/// Script output is appended to the console even if a script fails
+pub fn prepare(request_data: &RequestData, collection_data: &CollectionData, console: &mut Vec<String>) -> anyhow::Result<PreparedRequest> {
+    let mut context = ScriptContext {
+        request_data: request_data.clone(),
+        variables: collection_data.resolved_variables(),
+        console: vec![],
+    };
+    let result = script::run(&collection_data.pre_request_script, &mut context)
+        .and_then(|_| script::run(&request_data.pre_request_script, &mut context));
+    console.append(&mut context.console);
+    result?;
+
+    let ScriptContext { mut request_data, variables, .. } = context;
+    if request_data.selected_auth == AuthType::Inherit {
+        request_data.selected_auth = collection_data.selected_auth.clone();
+        if let Some(auth) = collection_data.auth.get(&collection_data.selected_auth) {
+            request_data.auth.insert(collection_data.selected_auth.clone(), auth.clone());
+        }
+    }
+    request_data.headers = with_inherited_headers(&request_data.headers, &collection_data.headers);
+    substitute_request(&mut request_data, &variables);
+
+    Ok(PreparedRequest { request_data, variables })
+}
+
+/// The inherited headers the request does not set itself, followed by the headers of the request
+pub fn with_inherited_headers(headers: &[(String, String)], inherited: &[(String, String)]) -> Vec<(String, String)> {
+    inherited.iter()
+        .filter(|(name, _)| !headers.iter().any(|(own, _)| own.eq_ignore_ascii_case(name)))
+        .chain(headers)
+        .cloned()
+        .collect()
+}
+
+/// Returns the credentials the request is sent with, following `Inherit` to the collection
+pub fn resolve_auth<'a>(request_data: &'a RequestData, collection_data: &'a CollectionData) -> Option<&'a AuthData> {
+    match request_data.selected_auth {
+        AuthType::None => None,
+        AuthType::Inherit => collection_data.auth.get(&collection_data.selected_auth),
+        _ => request_data.auth.get(&request_data.selected_auth),
+    }
+}
+
+/// The computed `Authorization` header value, if the selected auth produces one
+pub fn auth_header(request_data: &RequestData, collection_data: &CollectionData) -> anyhow::Result<Option<String>> {
+    match resolve_auth(request_data, collection_data) {
+        Some(auth) => auth.to_header(),
+        None => Ok(None),
+    }
+}
+
+/// Adds the credentials to the outgoing headers, replacing any `Authorization` header set by hand
+pub fn apply_auth(headers: &mut HeaderMap, request_data: &RequestData, collection_data: &CollectionData) -> anyhow::Result<()> {
+    if let Some(value) = auth_header(request_data, collection_data)? {
+        let value = HeaderValue::from_str(&value).context("Invalid characters in the credentials")?;
+        headers.insert(AUTHORIZATION, value);
+    }
+    Ok(())
+}
+
+/// Builds the outgoing request from data that was already [prepared](prepare)
+pub fn build_request(client: &Client, request_data: &RequestData, collection_data: &CollectionData) -> anyhow::Result<Request> {
+    let mut headers = HeaderMap::new();
+    for (key, value) in &request_data.headers {
+        let header_name = HeaderName::from_bytes(key.as_bytes())
+            .with_context(|| format!("Invalid header name \"{}\"", key))?;
+        let header_value = HeaderValue::from_str(value)
+            .with_context(|| format!("Invalid value for header \"{}\"", key))?;
+        headers.append(header_name, header_value);
+    }
+    apply_auth(&mut headers, request_data, collection_data)?;
+
+    let body = request_data.body.get(&request_data.selected_body).map(|b| b.clone().to_body()).unwrap_or_default();
+
+    let request = client
+        .request(request_data.method.clone().into(), request_data.url_string.clone())
+        .headers(headers)
+        .body(body)
+        .build()?;
+    Ok(request)
+}
+
+/// The client every request is sent with. Certificates are not verified, since most APIs under
+/// development use self-signed ones
+pub fn client() -> anyhow::Result<Client> {
+    let client = Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()?;
+    Ok(client)
+}
+
+/// A client that rejects invalid certificates, for runs outside the app unless they opt out
+pub fn verifying_client() -> anyhow::Result<Client> {
+    Ok(Client::builder().build()?)
+}
+
+/// Sends the request and reads the whole response
+pub fn execute(client: &Client, request: Request) -> anyhow::Result<ResponseData> {
+    let start = Instant::now();
+    let response = client.execute(request)?;
+    let status = response.status().as_u16();
+    let headers = response_headers(&response);
+    let body = response.bytes()?.to_vec();
+
+    Ok(ResponseData { status, headers, size: body.len() as u64, body, duration: start.elapsed() })
+}
+
+/// How much of a download arrived. The UI reads it while another thread downloads
+#[derive(Debug, Default)]
+pub struct Progress {
+    pub received: AtomicU64,
+    /// 0 if the server didn't say how big the body is
+    pub total: AtomicU64,
+}
+
+/// Like [`execute`], but writes the body into the file while it arrives.
+/// Only the first [`PREVIEW_BYTES`] of it are kept in the response
+pub fn download(client: &Client, request: Request, path: &Path, progress: &Progress) -> anyhow::Result<ResponseData> {
+    let start = Instant::now();
+    let mut response = client.execute(request)?;
+    let status = response.status().as_u16();
+    let headers = response_headers(&response);
+    progress.total.store(response.content_length().unwrap_or(0), Ordering::Relaxed);
+
+    let file = File::create(path).with_context(|| format!("Could not create {}", path.display()))?;
+    let mut file = BufWriter::new(file);
+    let mut body = vec![];
+    let mut size = 0;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = match response.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e).context("Receiving the body failed"),
+        };
+        file.write_all(&buffer[..read]).with_context(|| format!("Could not write to {}", path.display()))?;
+        let keep = read.min(PREVIEW_BYTES - body.len());
+        body.extend_from_slice(&buffer[..keep]);
+        size += read as u64;
+        progress.received.store(size, Ordering::Relaxed);
+    }
+    file.flush().with_context(|| format!("Could not write to {}", path.display()))?;
+
+    Ok(ResponseData { status, headers, body, size, duration: start.elapsed() })
+}
+
+fn response_headers(response: &reqwest::blocking::Response) -> Vec<(String, String)> {
+    response.headers().iter()
+        .map(|(key, value)| (key.to_string(), String::from_utf8_lossy(value.as_bytes()).to_string()))
+        .collect()
+}
+
+/// Sends the request like the Send button does: runs the scripts, fills in the variables, applies the
+/// auth and checks the assertions. Variables changed by the scripts are written back to the collection
+pub fn run(client: &Client, request_data: &RequestData, collection_data: &mut CollectionData, console: &mut Vec<String>) -> anyhow::Result<Outcome> {
+    let prepared = prepare(request_data, collection_data, console)?;
+    collection_data.store_variables(prepared.variables.clone());
+    let request = build_request(client, &prepared.request_data, collection_data)?;
+    let response = execute(client, request)?;
+
+    Ok(Outcome {
+        test_results: assertions::evaluate_all(&prepared.request_data.assertions, &response),
+        response,
+        variables: prepared.variables,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use crate::test_support::stand_in;
+
+    use super::*;
+
+    #[test]
+    fn run_sends_the_prepared_request() {
+        let (address, server) = stand_in(|_| b"HTTP/1.1 201 Created\r\nContent-Type: application/json\r\nContent-Length: 21\r\n\r\n{\"id\": 7, \"ok\": true}".to_vec());
+
+        let mut request_data = RequestData {
+            method: RequestMethod::Post,
+            url_string: String::from("http://{{host}}/items"),
+            selected_auth: AuthType::Inherit,
+            selected_body: BodyType::Raw,
+            pre_request_script: String::from(r#"variables["name"] = "widget";"#),
+            assertions: vec![
+                Assertion::StatusEquals { status: 201 },
+                Assertion::JsonPathEquals { path: String::from("$.id"), value: String::from("7") },
+            ],
+            ..Default::default()
+        };
+        request_data.headers.push((String::from("X-Item"), String::from("{{name}}")));
+        request_data.body.insert(BodyType::Raw, BodyData::Raw { data: String::from("{\"name\": \"{{name}}\"}") });
+
+        let mut collection_data = CollectionData { selected_auth: AuthType::Bearer, ..Default::default() };
+        collection_data.auth.insert(AuthType::Bearer, AuthData::Bearer { token: String::from("{{token}}") });
+        collection_data.variables.push((String::from("host"), address));
+        collection_data.variables.push((String::from("token"), String::from("secret")));
+
+        let outcome = run(&client().unwrap(), &request_data, &mut collection_data, &mut vec![]).unwrap();
+        let received = server.recv().unwrap().to_lowercase();
+
+        assert!(received.starts_with("post /items http/1.1"));
+        assert!(received.contains("authorization: bearer secret"));
+        assert!(received.contains("x-item: widget"));
+        assert!(received.ends_with("{\"name\": \"widget\"}"));
+
+        assert_eq!(outcome.response.status, 201);
+        assert_eq!(outcome.response.header("content-type"), Some("application/json"));
+        assert_eq!(outcome.response.json().unwrap()["ok"], true);
+        assert!(outcome.passed(), "{:?}", outcome.test_results);
+        // The variable set by the script is kept for the next request
+        assert!(collection_data.variables.contains(&(String::from("name"), String::from("widget"))));
+    }
+
+    #[test]
+    fn failed_assertions_are_reported() {
+        let (address, server) = stand_in(|_| b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec());
+        let request_data = RequestData {
+            url_string: format!("http://{}/missing", address),
+            assertions: vec![Assertion::StatusEquals { status: 200 }],
+            ..Default::default()
+        };
+
+        let outcome = run(&client().unwrap(), &request_data, &mut CollectionData::default(), &mut vec![]).unwrap();
+        server.recv().unwrap();
+
+        assert_eq!(outcome.response.status, 404);
+        assert!(!outcome.passed());
+    }
+
+    #[test]
+    fn binary_bodies_are_kept() {
+        let (address, server) = stand_in(|_| b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\n\x89PN\xff".to_vec());
+        let request_data = RequestData { url_string: format!("http://{}/", address), ..Default::default() };
+
+        let client = client().unwrap();
+        let request = build_request(&client, &request_data, &CollectionData::default()).unwrap();
+        let response = execute(&client, request).unwrap();
+        server.recv().unwrap();
+
+        assert_eq!(response.body, b"\x89PN\xff");
+        assert!(response.json().is_err());
+    }
+
+    #[test]
+    fn downloads_go_into_the_file() {
+        let body = "x".repeat(PREVIEW_BYTES + 10);
+        let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+        let (address, server) = stand_in(move |_| response.clone().into_bytes());
+        let request_data = RequestData { url_string: format!("http://{}/", address), ..Default::default() };
+        let path = std::env::temp_dir().join(format!("packets-download-{}", uuid::Uuid::new_v4()));
+
+        let client = client().unwrap();
+        let request = build_request(&client, &request_data, &CollectionData::default()).unwrap();
+        let progress = Progress::default();
+        let response = download(&client, request, &path, &progress).unwrap();
+        server.recv().unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(written, body);
+        assert_eq!(response.body.len(), PREVIEW_BYTES);
+        assert_eq!(response.size, body.len() as u64);
+        assert!(response.is_truncated());
+        assert_eq!(progress.received.load(Ordering::Relaxed), body.len() as u64);
+        assert_eq!(progress.total.load(Ordering::Relaxed), body.len() as u64);
+    }
+
+    #[test]
+    fn unreachable_server_is_an_error() {
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let request_data = RequestData { url_string: format!("http://{}/", address), ..Default::default() };
+        assert!(run(&client().unwrap(), &request_data, &mut CollectionData::default(), &mut vec![]).is_err());
+    }
+
+    fn request_with_auth(auth_type: AuthType, auth_data: AuthData) -> RequestData {
+        let mut request_data = RequestData {
+            url_string: String::from("http://localhost/"),
+            selected_auth: auth_type.clone(),
+            ..Default::default()
+        };
+        request_data.auth.insert(auth_type, auth_data);
+        request_data
+    }
+
+    #[test]
+    fn none_adds_no_header() {
+        let request_data = request_with_auth(AuthType::None, AuthData::None);
+        assert_eq!(auth_header(&request_data, &CollectionData::default()).unwrap(), None);
+
+        let request = build_request(&Client::new(), &request_data, &CollectionData::default()).unwrap();
+        assert!(request.headers().get(AUTHORIZATION).is_none());
+    }
+
+    #[test]
+    fn basic_is_standard_base64_with_prefix() {
+        let request_data = request_with_auth(AuthType::Basic, AuthData::Basic {
+            username: String::from("Aladdin"),
+            password: String::from("open sesame"),
+        });
+        assert_eq!(
+            auth_header(&request_data, &CollectionData::default()).unwrap().as_deref(),
+            Some("Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==")
+        );
+    }
+
+    #[test]
+    fn bearer_has_prefix() {
+        let request_data = request_with_auth(AuthType::Bearer, AuthData::Bearer { token: String::from("abc.def") });
+        assert_eq!(auth_header(&request_data, &CollectionData::default()).unwrap().as_deref(), Some("Bearer abc.def"));
+    }
+
+    #[test]
+    fn jwt_is_signed_with_iat_and_exp() {
+        let request_data = request_with_auth(AuthType::Jwt, AuthData::Jwt {
+            algorithm: JwtAlgorithm::HS256,
+            key: String::from("secret"),
+            claims: String::from(r#"{"sub": "service"}"#),
+            expires_in: 60,
+        });
+        let header = auth_header(&request_data, &CollectionData::default()).unwrap().unwrap();
+        let token = header.strip_prefix("Bearer ").unwrap();
+
+        let decoded = crate::jwt::DecodedJwt::decode(token).unwrap();
+        assert_eq!(decoded.header["alg"], "HS256");
+        assert_eq!(decoded.claims["sub"], "service");
+        let iat = decoded.claims["iat"].as_u64().unwrap();
+        assert_eq!(decoded.expiry(), Some(iat + 60));
+    }
+
+    #[test]
+    fn jwt_with_invalid_claims_is_an_error() {
+        let request_data = request_with_auth(AuthType::Jwt, AuthData::Jwt {
+            algorithm: JwtAlgorithm::HS256,
+            key: String::from("secret"),
+            claims: String::from("not json"),
+            expires_in: 60,
+        });
+        assert!(auth_header(&request_data, &CollectionData::default()).is_err());
+    }
+
+    #[test]
+    fn inherit_uses_collection_auth() {
+        let request_data = RequestData { selected_auth: AuthType::Inherit, ..Default::default() };
+        let mut collection_data = CollectionData { selected_auth: AuthType::Bearer, ..Default::default() };
+        collection_data.auth.insert(AuthType::Bearer, AuthData::Bearer { token: String::from("shared") });
+
+        assert_eq!(auth_header(&request_data, &collection_data).unwrap().as_deref(), Some("Bearer shared"));
+        assert_eq!(auth_header(&request_data, &CollectionData::default()).unwrap(), None);
+    }
+
+    #[test]
+    fn auth_is_applied_without_touching_request_data() {
+        let mut request_data = request_with_auth(AuthType::Bearer, AuthData::Bearer { token: String::from("abc") });
+        request_data.headers.push((String::from("Authorization"), String::from("manual")));
+        request_data.headers.push((String::from("Accept"), String::from("*/*")));
+        let before = request_data.clone();
+
+        let request = build_request(&Client::new(), &request_data, &CollectionData::default()).unwrap();
+
+        assert_eq!(request.headers().get_all(AUTHORIZATION).iter().collect::<Vec<_>>(), vec!["Bearer abc"]);
+        assert_eq!(request.headers().get("Accept").unwrap(), "*/*");
+        assert_eq!(request_data, before);
+    }
+
+    #[test]
+    fn prepared_inherit_keeps_collection_auth() {
+        let request_data = RequestData { selected_auth: AuthType::Inherit, ..Default::default() };
+        let mut collection_data = CollectionData { selected_auth: AuthType::Bearer, ..Default::default() };
+        collection_data.auth.insert(AuthType::Bearer, AuthData::Bearer { token: String::from("{{token}}") });
+        collection_data.variables.push((String::from("token"), String::from("shared")));
+
+        let prepared = prepare(&request_data, &collection_data, &mut vec![]).unwrap();
+        assert_eq!(auth_header(&prepared.request_data, &CollectionData::default()).unwrap().as_deref(), Some("Bearer shared"));
+    }
+
+    #[test]
+    fn folders_override_the_collection() {
+        let request_data = RequestData {
+            selected_auth: AuthType::Inherit,
+            headers: vec![(String::from("accept"), String::from("text/plain"))],
+            ..Default::default()
+        };
+        let mut collection_data = CollectionData { selected_auth: AuthType::Bearer, ..Default::default() };
+        collection_data.auth.insert(AuthType::Bearer, AuthData::Bearer { token: String::from("{{token}}") });
+        collection_data.variables.push((String::from("token"), String::from("collection")));
+        collection_data.headers.push((String::from("Accept"), String::from("application/json")));
+        collection_data.headers.push((String::from("X-Client"), String::from("packets")));
+
+        let outer = FolderData {
+            variables: vec![(String::from("token"), String::from("outer"))],
+            headers: vec![(String::from("X-Client"), String::from("{{token}}"))],
+            ..Default::default()
+        };
+        let inner = FolderData { selected_auth: AuthType::None, ..Default::default() };
+
+        let prepared = prepare(&request_data, &collection_data.scoped([&outer]), &mut vec![]).unwrap();
+        assert_eq!(auth_header(&prepared.request_data, &collection_data).unwrap().as_deref(), Some("Bearer outer"));
+        assert_eq!(prepared.request_data.headers, vec![
+            (String::from("X-Client"), String::from("outer")),
+            (String::from("accept"), String::from("text/plain")),
+        ]);
+
+        let prepared = prepare(&request_data, &collection_data.scoped([&outer, &inner]), &mut vec![]).unwrap();
+        assert_eq!(auth_header(&prepared.request_data, &collection_data).unwrap(), None);
+    }
+
+    #[test]
+    fn script_variables_go_back_to_where_they_are_defined() {
+        let mut collection_data = CollectionData::default();
+        collection_data.variables.push((String::from("shared"), String::from("1")));
+        let mut folder = FolderData { variables: vec![(String::from("local"), String::from("1"))], ..Default::default() };
+
+        let mut scoped = collection_data.scoped([&folder]);
+        scoped.store_variables(BTreeMap::from([
+            (String::from("shared"), String::from("2")),
+            (String::from("local"), String::from("2")),
+            (String::from("new"), String::from("2")),
+        ]));
+        collection_data.unscope([&mut folder], scoped);
+
+        assert_eq!(folder.variables, vec![(String::from("local"), String::from("2"))]);
+        assert_eq!(collection_data.variables, vec![
+            (String::from("shared"), String::from("2")),
+            (String::from("new"), String::from("2")),
+        ]);
+    }
+
+    #[test]
+    fn invalid_header_is_an_error() {
+        let mut request_data = RequestData { url_string: String::from("http://localhost/"), ..Default::default() };
+        request_data.headers.push((String::from("Bad Header"), String::from("value")));
+        assert!(build_request(&Client::new(), &request_data, &CollectionData::default()).is_err());
+    }
+}
@@ -0,0 +1,288 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Finding text in the response and request bodies
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::sync::Arc;
+
+use egui::text::{CCursor, LayoutJob};
+use egui::text_edit::TextEditOutput;
+use egui::{Align, Color32, Galley, Key, TextEdit, Ui};
+use regex::RegexBuilder;
+
+/// More matches aren't counted, so finding a single letter in a large body stays fast
+const MAX_MATCHES: usize = 10_000;
+
+/// Where the query is in the text. Empty matches of a regular expression are left out
+pub fn find_all(text: &str, query: &str, match_case: bool, regex: bool) -> Result<Vec<Range<usize>>, String> {
+    if query.is_empty() {
+        return Ok(vec![]);
+    }
+    let pattern = match regex {
+        true => query.to_owned(),
+        false => regex::escape(query),
+    };
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(!match_case)
+        .multi_line(true)
+        .build()
+        .map_err(|e| e.to_string())?;
+    Ok(regex.find_iter(text)
+        .map(|found| found.range())
+        .filter(|range| !range.is_empty())
+        .take(MAX_MATCHES)
+        .collect())
+}
+
+/// Gives the matches a background. The current one stands out. Matches past the end of the text are left out,
+/// so the text can be the start of the one that was searched
+pub fn mark(job: &mut LayoutJob, matches: &[Range<usize>], current: Option<usize>, colors: (Color32, Color32)) {
+    let text = &job.text;
+    let matches = &matches[..matches.partition_point(|m| m.end <= text.len())];
+    let valid = |range: &Range<usize>| range.end <= text.len() && text.is_char_boundary(range.start) && text.is_char_boundary(range.end);
+    // The text may have been edited since the matches were found
+    if matches.is_empty() || !matches.iter().all(valid) {
+        return;
+    }
+    let mut sections = vec![];
+    let mut next = 0;
+    for section in std::mem::take(&mut job.sections) {
+        let mut start = section.byte_range.start;
+        while start < section.byte_range.end {
+            while matches.get(next).map_or(false, |m| m.end <= start) {
+                next += 1;
+            }
+            let (end, background) = match matches.get(next) {
+                Some(m) if m.start <= start => {
+                    let color = if current == Some(next) { colors.1 } else { colors.0 };
+                    (m.end, Some(color))
+                },
+                Some(m) => (m.start, None),
+                None => (section.byte_range.end, None),
+            };
+            let mut part = section.clone();
+            part.byte_range = start..end.min(section.byte_range.end);
+            if start != section.byte_range.start {
+                part.leading_space = 0.;
+            }
+            if let Some(background) = background {
+                part.format.background = background;
+            }
+            start = part.byte_range.end;
+            sections.push(part);
+        }
+    }
+    job.sections = sections;
+}
+
+/// A bar above a text to find things in it
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct FindBar {
+    pub open: bool,
+    query: String,
+    match_case: bool,
+    regex: bool,
+    matches: Vec<Range<usize>>,
+    error: Option<String>,
+    /// Index into the matches
+    current: usize,
+    /// The hash of what was searched and the settings the matches were found with
+    searched: Option<u64>,
+    /// Moves the focus into the query on the next frame
+    focus: bool,
+    /// Scrolls to the current match the next time the text is drawn
+    scroll: bool,
+    /// True if the query has the focus
+    pub has_focus: bool,
+}
+
+impl FindBar {
+    pub fn open(&mut self) {
+        self.open = true;
+        self.focus = true;
+        self.scroll = true;
+    }
+
+    fn close(&mut self) {
+        self.open = false;
+        self.has_focus = false;
+        self.matches.clear();
+        self.searched = None;
+    }
+
+    fn step(&mut self, forward: bool) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let count = self.matches.len();
+        self.current = match forward {
+            true => (self.current + 1) % count,
+            false => (self.current + count - 1) % count,
+        };
+        self.scroll = true;
+    }
+
+    /// Shows the bar if it is open and finds the query in the text. The text can be edited,
+    /// so it is searched again whenever it changes
+    pub fn render(&mut self, ui: &mut Ui, text: &str) {
+        self.render_preview(ui, text, text.len(), text);
+    }
+
+    /// Like `render` for a text of which only the first `shown` bytes are shown.
+    /// Matches after them are counted, but can't be marked. The text is only searched again
+    /// when `source` changes, so a large text isn't hashed every frame
+    pub fn render_preview(&mut self, ui: &mut Ui, text: &str, shown: usize, source: impl Hash) {
+        if !self.open {
+            return;
+        }
+        let mut hasher = DefaultHasher::new();
+        (source, &self.query, self.match_case, self.regex).hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.searched != Some(hash) {
+            match find_all(text, &self.query, self.match_case, self.regex) {
+                Ok(matches) => {
+                    self.matches = matches;
+                    self.error = None;
+                },
+                Err(e) => {
+                    self.matches.clear();
+                    self.error = Some(e);
+                },
+            }
+            self.current = self.current.min(self.matches.len().saturating_sub(1));
+            self.searched = Some(hash);
+            self.scroll = true;
+        }
+
+        ui.horizontal(|ui| {
+            let query = ui.add(TextEdit::singleline(&mut self.query).hint_text("Find").desired_width(200.));
+            if std::mem::take(&mut self.focus) {
+                query.request_focus();
+            }
+            if query.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                self.step(!ui.input(|i| i.modifiers.shift));
+                // Keep typing Enter to go through the matches
+                query.request_focus();
+            }
+            // The query gives up the focus on Escape
+            if query.lost_focus() && ui.input(|i| i.key_pressed(Key::Escape)) {
+                self.close();
+                return;
+            }
+            self.has_focus = query.has_focus();
+
+            ui.toggle_value(&mut self.match_case, "Aa").on_hover_text("Match Case");
+            ui.toggle_value(&mut self.regex, ".*").on_hover_text("Regular Expression");
+            match &self.error {
+                Some(e) => {
+                    ui.colored_label(Color32::RED, "Invalid expression").on_hover_text(e);
+                },
+                None if self.matches.is_empty() && !self.query.is_empty() => {
+                    ui.weak("No matches");
+                },
+                None if !self.matches.is_empty() => {
+                    let more = if self.matches.len() == MAX_MATCHES { "+" } else { "" };
+                    ui.label(format!("{} of {}{}", self.current + 1, self.matches.len(), more));
+                    let hidden = self.matches.len() - self.matches.partition_point(|m| m.end <= shown);
+                    if hidden > 0 {
+                        ui.weak(format!("{} after the preview", hidden));
+                    }
+                },
+                None => {},
+            }
+            if ui.button("⬆").on_hover_text("Previous Match (Shift+Enter)").clicked() {
+                self.step(false);
+            }
+            if ui.button("⬇").on_hover_text("Next Match (Enter)").clicked() {
+                self.step(true);
+            }
+            if ui.button("✕").on_hover_text("Close (Escape)").clicked() {
+                self.close();
+            }
+        });
+    }
+
+    /// Lays out the text of a `TextEdit` with the matches marked
+    pub fn layout(&self, ui: &Ui, mut job: LayoutJob, wrap_width: f32) -> Arc<Galley> {
+        if self.open {
+            let visuals = ui.visuals();
+            let colors = match visuals.dark_mode {
+                true => (Color32::from_rgba_unmultiplied(200, 170, 0, 70), Color32::from_rgb(150, 110, 0)),
+                false => (Color32::from_rgb(255, 240, 150), Color32::from_rgb(255, 190, 60)),
+            };
+            mark(&mut job, &self.matches, Some(self.current), colors);
+        }
+        job.wrap.max_width = wrap_width;
+        ui.fonts(|fonts| fonts.layout_job(job))
+    }
+
+    /// Call with the output of the `TextEdit` the text was shown in
+    pub fn scroll_to_match(&mut self, ui: &Ui, output: &TextEditOutput) {
+        if !self.open || !std::mem::take(&mut self.scroll) {
+            return;
+        }
+        let text = output.galley.text();
+        let Some(range) = self.matches.get(self.current).filter(|range| text.is_char_boundary(range.start) && range.end <= text.len()) else {
+            return;
+        };
+        let galley = &output.galley;
+        let cursor = galley.from_ccursor(CCursor::new(text[..range.start].chars().count()));
+        let rect = galley.pos_from_cursor(&cursor).translate(output.text_draw_pos.to_vec2());
+        ui.scroll_to_rect(rect, Some(Align::Center));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::text::TextFormat;
+
+    use super::*;
+
+    #[test]
+    fn finds_text_and_expressions() {
+        let text = "Id: 1\nid: 22\nID: 333";
+        assert_eq!(find_all(text, "id", false, false).unwrap(), vec![0..2, 6..8, 13..15]);
+        assert_eq!(find_all(text, "id", true, false).unwrap(), vec![6..8]);
+        assert_eq!(find_all(text, r"^\w+: \d{2,}$", false, true).unwrap(), vec![6..12, 13..20]);
+        assert!(find_all(text, "x*", false, true).unwrap().is_empty());
+        assert_eq!(find_all("a.b", ".", false, false).unwrap(), vec![1..2]);
+        assert!(find_all(text, "(", false, true).is_err());
+        assert!(find_all(text, "", false, true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn marking_splits_sections() {
+        let mut job = LayoutJob::default();
+        job.append("abc", 0., TextFormat::default());
+        job.append("def", 0., TextFormat::default());
+        let (other, current) = (Color32::RED, Color32::GREEN);
+        mark(&mut job, &[1..4, 5..6], Some(1), (other, current));
+        let parts: Vec<(Range<usize>, Color32)> = job.sections.iter()
+            .map(|section| (section.byte_range.clone(), section.format.background))
+            .collect();
+        assert_eq!(parts, vec![
+            (0..1, Color32::TRANSPARENT),
+            (1..3, other),
+            (3..4, other),
+            (4..5, Color32::TRANSPARENT),
+            (5..6, current),
+        ]);
+
+        // Only the start of the text that was searched is shown
+        let mut job = LayoutJob::default();
+        job.append("abcdef", 0., TextFormat::default());
+        mark(&mut job, &[1..2, 5..8, 9..10], None, (other, current));
+        let parts: Vec<(Range<usize>, Color32)> = job.sections.iter()
+            .map(|section| (section.byte_range.clone(), section.format.background))
+            .collect();
+        assert_eq!(parts, vec![
+            (0..1, Color32::TRANSPARENT),
+            (1..2, other),
+            (2..6, Color32::TRANSPARENT),
+        ]);
+    }
+}
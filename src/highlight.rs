@@ -0,0 +1,560 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Syntax highlighting for the code editors of request and response bodies
+
+use std::ops::Range;
+
+use egui::text::{LayoutJob, TextFormat};
+use egui::util::cache::{ComputerMut, FrameCache};
+use egui::{Color32, FontId, TextStyle, Ui};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    Plain,
+    Json,
+    /// Also used for HTML
+    Xml,
+    GraphQl,
+    Yaml,
+    JavaScript,
+}
+
+impl Language {
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        let language = match mime.as_str() {
+            "application/json" => Language::Json,
+            "text/html" | "application/xml" | "text/xml" => Language::Xml,
+            "application/graphql" => Language::GraphQl,
+            "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => Language::Yaml,
+            "application/javascript" | "text/javascript" | "application/ecmascript" => Language::JavaScript,
+            _ if mime.ends_with("+json") => Language::Json,
+            _ if mime.ends_with("+xml") => Language::Xml,
+            _ => return None,
+        };
+        Some(language)
+    }
+
+    /// Guesses the language from the start of the text
+    pub fn guess(text: &str) -> Self {
+        let text = text.trim_start();
+        let first_word = text.split(|c: char| !c.is_alphabetic()).next().unwrap_or_default();
+        if text.starts_with(['{', '[']) {
+            Language::Json
+        } else if text.starts_with('<') {
+            Language::Xml
+        } else if ["query", "mutation", "subscription", "fragment"].contains(&first_word) {
+            Language::GraphQl
+        } else if text.starts_with("---") || text.lines().next().map_or(false, |line| line.trim_end().ends_with(':') || line.contains(": ")) {
+            Language::Yaml
+        } else {
+            Language::Plain
+        }
+    }
+
+    /// The language from the `Content-Type`, or a guess if there is none or it is unknown
+    pub fn detect(content_type: Option<&str>, text: &str) -> Self {
+        content_type.and_then(Self::from_content_type).unwrap_or_else(|| Self::guess(text))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Plain,
+    Keyword,
+    String,
+    Number,
+    Comment,
+    Punctuation,
+    /// Object keys and YAML keys
+    Key,
+    Tag,
+    Attribute,
+    /// A `{{variable}}` of the environment
+    Variable,
+}
+
+pub fn color(ui: &Ui, kind: Kind) -> Color32 {
+    let dark = ui.visuals().dark_mode;
+    match (kind, dark) {
+        (Kind::Plain | Kind::Punctuation, _) => ui.visuals().text_color(),
+        (Kind::Comment, _) => ui.visuals().weak_text_color(),
+        (Kind::Keyword, true) => Color32::from_rgb(97, 175, 239),
+        (Kind::Keyword, false) => Color32::from_rgb(20, 90, 190),
+        (Kind::String, true) => Color32::from_rgb(152, 195, 121),
+        (Kind::String, false) => Color32::from_rgb(80, 130, 40),
+        (Kind::Number, true) => Color32::from_rgb(209, 154, 102),
+        (Kind::Number, false) => Color32::from_rgb(170, 90, 0),
+        (Kind::Key | Kind::Tag, true) => Color32::from_rgb(224, 108, 117),
+        (Kind::Key | Kind::Tag, false) => Color32::from_rgb(170, 40, 50),
+        (Kind::Attribute, true) => Color32::from_rgb(198, 120, 221),
+        (Kind::Attribute, false) => Color32::from_rgb(130, 50, 160),
+        (Kind::Variable, true) => Color32::from_rgb(86, 182, 194),
+        (Kind::Variable, false) => Color32::from_rgb(0, 120, 130),
+    }
+}
+
+/// The highlighted text for a `TextEdit::layouter`. `variables` marks `{{variables}}`, which only mean something in requests
+pub fn job(ui: &Ui, text: &str, language: Language, variables: bool) -> LayoutJob {
+    let font = TextStyle::Monospace.resolve(ui.style());
+    let colors = COLORED_KINDS.map(|kind| color(ui, kind));
+    ui.ctx().memory_mut(|memory| {
+        memory.caches.cache::<FrameCache<LayoutJob, Highlighter>>().get((text, language, variables, &font, colors))
+    })
+}
+
+/// Every kind in the order they are declared, so `kind as usize` finds its color
+const COLORED_KINDS: [Kind; 10] = [
+    Kind::Plain,
+    Kind::Keyword,
+    Kind::String,
+    Kind::Number,
+    Kind::Comment,
+    Kind::Punctuation,
+    Kind::Key,
+    Kind::Tag,
+    Kind::Attribute,
+    Kind::Variable,
+];
+
+#[derive(Default)]
+struct Highlighter;
+
+impl ComputerMut<(&str, Language, bool, &FontId, [Color32; 10]), LayoutJob> for Highlighter {
+    fn compute(&mut self, (text, language, variables, font, colors): (&str, Language, bool, &FontId, [Color32; 10])) -> LayoutJob {
+        let mut job = LayoutJob::default();
+        let mut spans = tokens(text, language);
+        if variables {
+            spans = overlay(spans, variable_ranges(text));
+        }
+        let mut append = |range: Range<usize>, kind: Kind| {
+            let color = colors[kind as usize];
+            job.append(&text[range], 0., TextFormat::simple(font.clone(), color));
+        };
+        let mut end = 0;
+        for (range, kind) in spans {
+            if range.start > end {
+                append(end..range.start, Kind::Plain);
+            }
+            end = range.end;
+            append(range, kind);
+        }
+        if end < text.len() {
+            append(end..text.len(), Kind::Plain);
+        }
+        job
+    }
+}
+
+/// The parts of the text to color, in order and without overlaps. Everything else is plain
+pub fn tokens(text: &str, language: Language) -> Vec<(Range<usize>, Kind)> {
+    match language {
+        Language::Plain => vec![],
+        Language::Json => code(text, &JSON),
+        Language::JavaScript => code(text, &JAVASCRIPT),
+        Language::GraphQl => code(text, &GRAPHQL),
+        Language::Yaml => yaml(text),
+        Language::Xml => xml(text),
+    }
+}
+
+/// Every `{{name}}`, found the same way they are filled in when the request is sent
+fn variable_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find("{{") {
+        let start = offset + start;
+        let Some(end) = text[start..].find("}}") else {
+            break;
+        };
+        offset = start + end + 2;
+        ranges.push(start..offset);
+    }
+    ranges
+}
+
+/// Puts the variables over the other tokens, cutting them where they overlap
+fn overlay(spans: Vec<(Range<usize>, Kind)>, variables: Vec<Range<usize>>) -> Vec<(Range<usize>, Kind)> {
+    let mut result = vec![];
+    for (range, kind) in spans {
+        let mut start = range.start;
+        for variable in variables.iter().filter(|v| v.start < range.end && v.end > range.start) {
+            if variable.start > start {
+                result.push((start..variable.start, kind));
+            }
+            start = variable.end;
+        }
+        if start < range.end {
+            result.push((start..range.end, kind));
+        }
+    }
+    result.extend(variables.into_iter().map(|range| (range, Kind::Variable)));
+    result.sort_by_key(|(range, _)| range.start);
+    result
+}
+
+/// How a C-like language is split into tokens
+struct Syntax {
+    line_comment: Option<&'static str>,
+    block_comments: bool,
+    quotes: &'static [char],
+    keywords: &'static [&'static str],
+    /// `$name` is a variable, like in GraphQL
+    dollar_variables: bool,
+}
+
+const JSON: Syntax = Syntax {
+    line_comment: None,
+    block_comments: false,
+    quotes: &['"'],
+    keywords: &["true", "false", "null"],
+    dollar_variables: false,
+};
+
+const JAVASCRIPT: Syntax = Syntax {
+    line_comment: Some("//"),
+    block_comments: true,
+    quotes: &['"', '\'', '`'],
+    keywords: &[
+        "async", "await", "break", "case", "catch", "class", "const", "continue", "default", "delete", "do", "else",
+        "export", "extends", "false", "finally", "for", "function", "if", "import", "in", "instanceof", "let", "new",
+        "null", "of", "return", "static", "super", "switch", "this", "throw", "true", "try", "typeof", "undefined",
+        "var", "void", "while", "yield",
+    ],
+    dollar_variables: false,
+};
+
+const GRAPHQL: Syntax = Syntax {
+    line_comment: Some("#"),
+    block_comments: false,
+    quotes: &['"'],
+    keywords: &[
+        "query", "mutation", "subscription", "fragment", "on", "type", "input", "enum", "interface", "union",
+        "scalar", "schema", "extend", "implements", "directive", "true", "false", "null",
+    ],
+    dollar_variables: true,
+};
+
+fn code(text: &str, syntax: &Syntax) -> Vec<(Range<usize>, Kind)> {
+    let mut spans = vec![];
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        let rest = &text[i..];
+        let (end, kind) = if syntax.line_comment.map_or(false, |comment| rest.starts_with(comment)) {
+            (rest.find('\n').map_or(text.len(), |end| i + end), Kind::Comment)
+        } else if syntax.block_comments && rest.starts_with("/*") {
+            (rest[2..].find("*/").map_or(text.len(), |end| i + end + 4), Kind::Comment)
+        } else if syntax.quotes.contains(&c) {
+            let end = string_end(text, i, c);
+            (end, if followed_by_colon(text, end) { Kind::Key } else { Kind::String })
+        } else if c.is_ascii_digit() || c == '-' && rest[1..].starts_with(|c: char| c.is_ascii_digit()) {
+            let len = 1 + rest[1..].find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_')).unwrap_or(rest.len() - 1);
+            (i + len, Kind::Number)
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let len = c.len_utf8() + rest[c.len_utf8()..].find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$')).unwrap_or(rest.len() - c.len_utf8());
+            let word = &rest[..len];
+            let kind = if syntax.keywords.contains(&word) {
+                Kind::Keyword
+            } else if syntax.dollar_variables && c == '$' {
+                Kind::Attribute
+            } else if followed_by_colon(text, i + len) {
+                Kind::Key
+            } else {
+                Kind::Plain
+            };
+            (i + len, kind)
+        } else if c.is_ascii_punctuation() {
+            (i + 1, Kind::Punctuation)
+        } else {
+            i += c.len_utf8();
+            continue;
+        };
+        if kind != Kind::Plain {
+            spans.push((i..end, kind));
+        }
+        i = end;
+    }
+    spans
+}
+
+/// The end of the string that starts at `start`, after its closing quote. Triple quotes end with three
+fn string_end(text: &str, start: usize, quote: char) -> usize {
+    let triple = quote.to_string().repeat(3);
+    if text[start..].starts_with(&triple) {
+        return text[start + 3..].find(&triple).map_or(text.len(), |end| start + 3 + end + 3);
+    }
+    let mut chars = text[start + 1..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            },
+            // Only template strings go on over line breaks
+            '\n' if quote != '`' => return start + 1 + i,
+            c if c == quote => return start + 1 + i + 1,
+            _ => {},
+        }
+    }
+    text.len()
+}
+
+fn followed_by_colon(text: &str, end: usize) -> bool {
+    text[end..].trim_start_matches([' ', '\t']).starts_with(':')
+}
+
+fn yaml(text: &str) -> Vec<(Range<usize>, Kind)> {
+    let mut spans = vec![];
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let line = line.trim_end_matches(['\n', '\r']);
+        let content_start = start + line.len() - line.trim_start().len();
+        let mut content = &text[content_start..start + line.len()];
+        let mut position = content_start;
+        let comment = yaml_comment(content).map(|i| {
+            content = &content[..i];
+            (content_start + i..start + line.len(), Kind::Comment)
+        });
+
+        if content.starts_with("---") || content.starts_with("...") {
+            spans.push((position..position + 3, Kind::Punctuation));
+        } else {
+            while let Some(item) = content.strip_prefix('-').filter(|item| item.is_empty() || item.starts_with(' ')) {
+                spans.push((position..position + 1, Kind::Punctuation));
+                let trimmed = item.trim_start();
+                position += content.len() - trimmed.len();
+                content = trimmed;
+            }
+            let value = match find_key_colon(content) {
+                Some(colon) => {
+                    spans.push((position..position + colon, Kind::Key));
+                    spans.push((position + colon..position + colon + 1, Kind::Punctuation));
+                    let value = &content[colon + 1..];
+                    let trimmed = value.trim_start();
+                    (position + colon + 1 + value.len() - trimmed.len(), trimmed.trim_end())
+                },
+                None => (position, content.trim_end()),
+            };
+            if let Some(kind) = yaml_value(value.1) {
+                spans.push((value.0..value.0 + value.1.len(), kind));
+            }
+        }
+        spans.extend(comment);
+    }
+    spans
+}
+
+/// A comment starts at a # at the start of the line or after a space, outside of quotes
+fn yaml_comment(content: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in content.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '#') if previous.is_whitespace() => return Some(i),
+            _ => {},
+        }
+        previous = c;
+    }
+    None
+}
+
+/// Where the colon after a key is, if the line has a key
+fn find_key_colon(content: &str) -> Option<usize> {
+    if content.starts_with(['"', '\'']) {
+        let end = string_end(content, 0, content.chars().next()?);
+        return content[end..].trim_start().starts_with(':')
+            .then(|| end + content[end..].find(':').unwrap_or(0));
+    }
+    let colon = content.find(": ").or_else(|| content.strip_suffix(':').map(|key| key.len()))?;
+    (!content[..colon].contains(['{', '[', '"', '\''])).then_some(colon)
+}
+
+fn yaml_value(value: &str) -> Option<Kind> {
+    if value.is_empty() {
+        return None;
+    }
+    let kind = match value {
+        "true" | "false" | "null" | "~" | "yes" | "no" => Kind::Keyword,
+        _ if value.parse::<f64>().is_ok() => Kind::Number,
+        _ if value.starts_with(['&', '*', '!']) => Kind::Attribute,
+        _ if value.starts_with(['|', '>']) && value.len() <= 2 => Kind::Punctuation,
+        _ => Kind::String,
+    };
+    Some(kind)
+}
+
+fn xml(text: &str) -> Vec<(Range<usize>, Kind)> {
+    let mut spans = vec![];
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        let rest = &text[i..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").map_or(text.len(), |end| i + 4 + end + 3);
+            spans.push((i..end, Kind::Comment));
+            i = end;
+        } else if rest.starts_with("<![CDATA[") {
+            let end = rest.find("]]>").map_or(text.len(), |end| i + end + 3);
+            spans.push((i..end, Kind::String));
+            i = end;
+        } else if c == '<' {
+            i = xml_tag(text, i, &mut spans);
+        } else if let Some(end) = rest.strip_prefix('&').and_then(|entity| {
+            entity.find(';').filter(|&end| end > 0 && end <= 10 && !entity[..end].contains(char::is_whitespace))
+        }) {
+            spans.push((i..i + end + 2, Kind::Keyword));
+            i += end + 2;
+        } else {
+            i += c.len_utf8();
+        }
+    }
+    spans
+}
+
+/// Colors the tag that starts at `start` and returns where it ends
+fn xml_tag(text: &str, start: usize, spans: &mut Vec<(Range<usize>, Kind)>) -> usize {
+    // `</name`, `<?xml` and `<!DOCTYPE` keep their first character
+    let prefix = if text[start + 1..].starts_with(['/', '?', '!']) { 2 } else { 1 };
+    let name_len = prefix + text[start + prefix..]
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .unwrap_or(text.len() - start - prefix);
+    spans.push((start..start + name_len, Kind::Tag));
+    let mut i = start + name_len;
+    while let Some(c) = text[i..].chars().next() {
+        let rest = &text[i..];
+        if c == '>' || rest.starts_with("/>") || rest.starts_with("?>") {
+            let len = if c == '>' { 1 } else { 2 };
+            spans.push((i..i + len, Kind::Tag));
+            return i + len;
+        } else if c == '"' || c == '\'' {
+            let end = rest[1..].find(c).map_or(text.len(), |end| i + end + 2);
+            spans.push((i..end, Kind::String));
+            i = end;
+        } else if c == '=' {
+            spans.push((i..i + 1, Kind::Punctuation));
+            i += 1;
+        } else if c.is_whitespace() {
+            i += c.len_utf8();
+        } else {
+            let len = rest.find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/').unwrap_or(rest.len()).max(c.len_utf8());
+            spans.push((i..i + len, Kind::Attribute));
+            i += len;
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(text: &str, language: Language) -> Vec<(&str, Kind)> {
+        tokens(text, language).into_iter().map(|(range, kind)| (&text[range], kind)).collect()
+    }
+
+    #[test]
+    fn json_keys_and_values() {
+        assert_eq!(kinds(r#"{"id": -1.5, "ok": true, "name": "a \" b"}"#, Language::Json), vec![
+            ("{", Kind::Punctuation),
+            ("\"id\"", Kind::Key),
+            (":", Kind::Punctuation),
+            ("-1.5", Kind::Number),
+            (",", Kind::Punctuation),
+            ("\"ok\"", Kind::Key),
+            (":", Kind::Punctuation),
+            ("true", Kind::Keyword),
+            (",", Kind::Punctuation),
+            ("\"name\"", Kind::Key),
+            (":", Kind::Punctuation),
+            ("\"a \\\" b\"", Kind::String),
+            ("}", Kind::Punctuation),
+        ]);
+    }
+
+    #[test]
+    fn xml_tags_and_attributes() {
+        assert_eq!(kinds(r#"<a href="/x">&amp;</a><!-- c --><br/>"#, Language::Xml), vec![
+            ("<a", Kind::Tag),
+            ("href", Kind::Attribute),
+            ("=", Kind::Punctuation),
+            ("\"/x\"", Kind::String),
+            (">", Kind::Tag),
+            ("&amp;", Kind::Keyword),
+            ("</a", Kind::Tag),
+            (">", Kind::Tag),
+            ("<!-- c -->", Kind::Comment),
+            ("<br", Kind::Tag),
+            ("/>", Kind::Tag),
+        ]);
+    }
+
+    #[test]
+    fn yaml_lines() {
+        assert_eq!(kinds("name: api # the name\nitems:\n  - 42\n  - key: \"v\"\n", Language::Yaml), vec![
+            ("name", Kind::Key),
+            (":", Kind::Punctuation),
+            ("api", Kind::String),
+            ("# the name", Kind::Comment),
+            ("items", Kind::Key),
+            (":", Kind::Punctuation),
+            ("-", Kind::Punctuation),
+            ("42", Kind::Number),
+            ("-", Kind::Punctuation),
+            ("key", Kind::Key),
+            (":", Kind::Punctuation),
+            ("\"v\"", Kind::String),
+        ]);
+    }
+
+    #[test]
+    fn graphql_and_javascript() {
+        assert_eq!(kinds("query Q($id: ID) { user(id: $id) # c\n}", Language::GraphQl), vec![
+            ("query", Kind::Keyword),
+            ("(", Kind::Punctuation),
+            ("$id", Kind::Attribute),
+            (":", Kind::Punctuation),
+            (")", Kind::Punctuation),
+            ("{", Kind::Punctuation),
+            ("(", Kind::Punctuation),
+            ("id", Kind::Key),
+            (":", Kind::Punctuation),
+            ("$id", Kind::Attribute),
+            (")", Kind::Punctuation),
+            ("# c", Kind::Comment),
+            ("}", Kind::Punctuation),
+        ]);
+        assert_eq!(kinds("const a = `x\ny`; // done", Language::JavaScript), vec![
+            ("const", Kind::Keyword),
+            ("=", Kind::Punctuation),
+            ("`x\ny`", Kind::String),
+            (";", Kind::Punctuation),
+            ("// done", Kind::Comment),
+        ]);
+    }
+
+    #[test]
+    fn variables_are_marked_inside_other_tokens() {
+        let text = r#"{"token": "Bearer {{token}}"}"#;
+        let spans = overlay(tokens(text, Language::Json), variable_ranges(text));
+        let spans: Vec<(&str, Kind)> = spans.into_iter().map(|(range, kind)| (&text[range], kind)).collect();
+        assert_eq!(&spans[3..], &[
+            ("\"Bearer ", Kind::String),
+            ("{{token}}", Kind::Variable),
+            ("\"", Kind::String),
+            ("}", Kind::Punctuation),
+        ]);
+    }
+
+    #[test]
+    fn detects_languages() {
+        assert_eq!(Language::detect(Some("application/vnd.api+json"), ""), Language::Json);
+        assert_eq!(Language::detect(Some("text/plain"), "<a/>"), Language::Xml);
+        assert_eq!(Language::detect(None, "query { me }"), Language::GraphQl);
+        assert_eq!(Language::detect(None, "openapi: 3.0.0"), Language::Yaml);
+        assert_eq!(Language::detect(None, "just words"), Language::Plain);
+    }
+}
@@ -0,0 +1,153 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! A small subset of JSONPath: `$`, `.key`, `['key']`, `[0]`, `[*]`, `.*` and `..key`.
+//! The jq forms `.`, `.[0]` and `.[]` work as well
+
+use anyhow::{anyhow, bail};
+use serde_json::Value;
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+    /// The value and everything in it
+    Descendants,
+}
+
+fn parse(path: &str) -> anyhow::Result<Vec<Segment>> {
+    let path = path.trim();
+    let mut rest = path.strip_prefix('$').unwrap_or(path);
+    let mut segments = vec![];
+
+    while !rest.is_empty() {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            if let Some(descendants) = after_dot.strip_prefix('.') {
+                segments.push(Segment::Descendants);
+                // Leave the dot in front of a key, so it is read as `.key` next
+                rest = match descendants.is_empty() || descendants.starts_with('[') {
+                    true => descendants,
+                    false => after_dot,
+                };
+                continue;
+            }
+            let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+            let key = &after_dot[..end];
+            match key {
+                "" if after_dot.starts_with('[') || path == "." => {},
+                "" => bail!("Empty key in \"{}\"", path),
+                "*" => segments.push(Segment::Wildcard),
+                _ => segments.push(Segment::Key(key.to_string())),
+            }
+            rest = &after_dot[end..];
+        } else if let Some(after_bracket) = rest.strip_prefix('[') {
+            let end = after_bracket.find(']').ok_or_else(|| anyhow!("Missing ] in \"{}\"", path))?;
+            let inner = after_bracket[..end].trim();
+            if inner == "*" || inner.is_empty() {
+                segments.push(Segment::Wildcard);
+            } else if let Some(quoted) = inner.strip_prefix(['\'', '"']).and_then(|i| i.strip_suffix(['\'', '"'])) {
+                segments.push(Segment::Key(quoted.to_string()));
+            } else {
+                let index = inner.parse().map_err(|_| anyhow!("Invalid index \"{}\" in \"{}\"", inner, path))?;
+                segments.push(Segment::Index(index));
+            }
+            rest = &after_bracket[end + 1..];
+        } else if segments.is_empty() {
+            // Allow leaving out the leading `$.`
+            return parse(&format!("$.{}", rest));
+        } else {
+            bail!("Unexpected \"{}\" in \"{}\"", rest, path);
+        }
+    }
+    Ok(segments)
+}
+
+/// Returns every value the path points to
+pub fn select<'a>(value: &'a Value, path: &str) -> anyhow::Result<Vec<&'a Value>> {
+    Ok(select_paths(value, path)?.into_iter().map(|(_, value)| value).collect())
+}
+
+/// Like [`select`], but also returns where each value is, like `$.items[0].id`
+pub fn select_paths<'a>(value: &'a Value, path: &str) -> anyhow::Result<Vec<(String, &'a Value)>> {
+    let mut current = vec![(String::from("$"), value)];
+    for segment in parse(path)? {
+        current = current.into_iter().flat_map(|(path, value)| -> Vec<(String, &Value)> {
+            match (&segment, value) {
+                (Segment::Key(key), Value::Object(map)) => map.get(key).map(|v| (child_path(&path, key), v)).into_iter().collect(),
+                (Segment::Index(index), Value::Array(array)) => array.get(*index).map(|v| (index_path(&path, *index), v)).into_iter().collect(),
+                (Segment::Wildcard, _) => children(&path, value),
+                (Segment::Descendants, _) => {
+                    let mut all = vec![];
+                    descendants(path, value, &mut all);
+                    all
+                },
+                _ => vec![],
+            }
+        }).collect();
+    }
+    Ok(current)
+}
+
+fn children<'a>(path: &str, value: &'a Value) -> Vec<(String, &'a Value)> {
+    match value {
+        Value::Object(map) => map.iter().map(|(key, v)| (child_path(path, key), v)).collect(),
+        Value::Array(array) => array.iter().enumerate().map(|(i, v)| (index_path(path, i), v)).collect(),
+        _ => vec![],
+    }
+}
+
+fn descendants<'a>(path: String, value: &'a Value, all: &mut Vec<(String, &'a Value)>) {
+    let children = children(&path, value);
+    all.push((path, value));
+    for (path, child) in children {
+        descendants(path, child, all);
+    }
+}
+
+/// The path of a key in the object at `path`. Keys that aren't plain names are quoted
+pub fn child_path(path: &str, key: &str) -> String {
+    let plain = !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+    match plain {
+        true => format!("{}.{}", path, key),
+        false => format!("{}[{:?}]", path, key),
+    }
+}
+
+pub fn index_path(path: &str, index: usize) -> String {
+    format!("{}[{}]", path, index)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn selects_with_paths() {
+        let value = json!({"items": [{"id": 1, "tags": {"id": 2}}, {"id": 3}], "total id": 4});
+        let ids: Vec<(String, Value)> = select_paths(&value, "$..id").unwrap()
+            .into_iter()
+            .map(|(path, value)| (path, value.clone()))
+            .collect();
+        assert_eq!(ids, vec![
+            (String::from("$.items[0].id"), json!(1)),
+            (String::from("$.items[0].tags.id"), json!(2)),
+            (String::from("$.items[1].id"), json!(3)),
+        ]);
+        assert_eq!(select_paths(&value, "$['total id']").unwrap()[0].0, "$[\"total id\"]");
+        assert_eq!(select(&value, "$..[1]").unwrap(), vec![&json!({"id": 3})]);
+    }
+
+    #[test]
+    fn understands_jq_paths() {
+        let value = json!({"items": [{"id": 1}, {"id": 3}]});
+        assert_eq!(select(&value, ".").unwrap(), vec![&value]);
+        assert_eq!(select(&value, ".items[].id").unwrap(), vec![&json!(1), &json!(3)]);
+        assert_eq!(select(&value, ".items[1]").unwrap(), vec![&json!({"id": 3})]);
+        assert!(select(&value, ".items[x]").is_err());
+        assert!(select(&value, "$.items.").is_err());
+    }
+}
@@ -5,9 +5,23 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 mod app;
+mod commands;
+mod diff;
+mod find;
+mod highlight;
 mod request;
 mod collection;
 mod tabs;
 mod tab_viewer;
+mod sidebar;
+mod jwt;
+mod script;
+mod json_path;
+mod search;
+mod workspace;
+pub mod engine;
+pub mod cli;
+#[cfg(test)]
+pub(crate) mod test_support;
 
 pub use app::PacketsApp;
@@ -0,0 +1,230 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::json_path;
+use crate::engine::ResponseData;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Assertion {
+    StatusEquals { status: u16 },
+    StatusInRange { from: u16, to: u16 },
+    HeaderPresent { name: String },
+    /// The pattern is a regular expression
+    HeaderMatches { name: String, pattern: String },
+    /// The expected value is compared as JSON if it parses, otherwise as a string
+    JsonPathEquals { path: String, value: String },
+    JsonPathExists { path: String },
+    BodyContains { text: String },
+    ResponseTimeBelow { millis: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssertionResult {
+    pub passed: bool,
+    pub message: String,
+}
+
+impl AssertionResult {
+    fn new(passed: bool, message: String) -> Self {
+        Self { passed, message }
+    }
+
+    /// Only the start of a downloaded body is kept, so what the rest holds is unknown
+    fn truncated() -> Self {
+        Self::new(false, String::from("Not evaluated (body truncated)"))
+    }
+}
+
+impl Assertion {
+    /// All kinds of assertions with default values, used to pick one in the UI
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::StatusEquals { status: 200 },
+            Self::StatusInRange { from: 200, to: 299 },
+            Self::HeaderPresent { name: String::new() },
+            Self::HeaderMatches { name: String::new(), pattern: String::new() },
+            Self::JsonPathEquals { path: String::from("$."), value: String::new() },
+            Self::JsonPathExists { path: String::from("$.") },
+            Self::BodyContains { text: String::new() },
+            Self::ResponseTimeBelow { millis: 1000 },
+        ]
+    }
+
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            Self::StatusEquals { .. } => "Status equals",
+            Self::StatusInRange { .. } => "Status in range",
+            Self::HeaderPresent { .. } => "Header present",
+            Self::HeaderMatches { .. } => "Header matches",
+            Self::JsonPathEquals { .. } => "JSON path equals",
+            Self::JsonPathExists { .. } => "JSON path exists",
+            Self::BodyContains { .. } => "Body contains",
+            Self::ResponseTimeBelow { .. } => "Response time below",
+        }
+    }
+
+    pub fn evaluate(&self, response: &ResponseData) -> AssertionResult {
+        let truncated = response.is_truncated();
+        match self {
+            Self::StatusEquals { status } => AssertionResult::new(
+                response.status == *status,
+                format!("Status is {}, expected {}", response.status, status),
+            ),
+            Self::StatusInRange { from, to } => AssertionResult::new(
+                (*from..=*to).contains(&response.status),
+                format!("Status is {}, expected {} to {}", response.status, from, to),
+            ),
+            Self::HeaderPresent { name } => match response.header(name) {
+                Some(_) => AssertionResult::new(true, format!("Header \"{}\" is present", name)),
+                None => AssertionResult::new(false, format!("Header \"{}\" is missing", name)),
+            },
+            Self::HeaderMatches { name, pattern } => {
+                let regex = match Regex::new(pattern) {
+                    Ok(r) => r,
+                    Err(e) => return AssertionResult::new(false, format!("Invalid pattern: {}", e)),
+                };
+                match response.header(name) {
+                    Some(value) => AssertionResult::new(
+                        regex.is_match(value),
+                        format!("Header \"{}\" is \"{}\", expected to match \"{}\"", name, value, pattern),
+                    ),
+                    None => AssertionResult::new(false, format!("Header \"{}\" is missing", name)),
+                }
+            },
+            Self::JsonPathEquals { .. } | Self::JsonPathExists { .. } if truncated => AssertionResult::truncated(),
+            Self::JsonPathEquals { path, value } => {
+                let expected: Value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.clone()));
+                match select(response, path) {
+                    Ok(found) => {
+                        let passed = found.contains(&expected);
+                        let actual = found.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ");
+                        AssertionResult::new(passed, format!("{} is [{}], expected {}", path, actual, expected))
+                    },
+                    Err(e) => AssertionResult::new(false, e),
+                }
+            },
+            Self::JsonPathExists { path } => match select(response, path) {
+                Ok(found) => AssertionResult::new(!found.is_empty(), format!("{} matched {} values", path, found.len())),
+                Err(e) => AssertionResult::new(false, e),
+            },
+            Self::BodyContains { text } => match response.text().contains(text.as_str()) {
+                true => AssertionResult::new(true, format!("Body contains \"{}\"", text)),
+                false if truncated => AssertionResult::truncated(),
+                false => AssertionResult::new(false, format!("Body doesn't contain \"{}\"", text)),
+            },
+            Self::ResponseTimeBelow { millis } => {
+                let took = response.duration.as_millis();
+                AssertionResult::new(took < *millis as u128, format!("Took {} ms, expected below {} ms", took, millis))
+            },
+        }
+    }
+}
+
+fn select(response: &ResponseData, path: &str) -> Result<Vec<Value>, String> {
+    let json = response.json().map_err(|e| format!("{:#}", e))?;
+    let found = json_path::select(&json, path).map_err(|e| e.to_string())?;
+    Ok(found.into_iter().cloned().collect())
+}
+
+pub fn evaluate_all(assertions: &[Assertion], response: &ResponseData) -> Vec<AssertionResult> {
+    assertions.iter().map(|a| a.evaluate(response)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn response() -> ResponseData {
+        let body = br#"{"user":{"id":7,"name":"Ada","tags":["admin"]}}"#.to_vec();
+        ResponseData {
+            status: 201,
+            headers: vec![(String::from("Content-Type"), String::from("application/json; charset=utf-8"))],
+            size: body.len() as u64,
+            body,
+            duration: Duration::from_millis(120),
+        }
+    }
+
+    fn check(assertion: Assertion) -> (bool, String) {
+        let result = assertion.evaluate(&response());
+        (result.passed, result.message)
+    }
+
+    fn text(s: &str) -> String {
+        s.to_owned()
+    }
+
+    #[test]
+    fn status() {
+        assert_eq!(check(Assertion::StatusEquals { status: 201 }), (true, text("Status is 201, expected 201")));
+        assert_eq!(check(Assertion::StatusEquals { status: 200 }), (false, text("Status is 201, expected 200")));
+        assert_eq!(check(Assertion::StatusInRange { from: 200, to: 299 }), (true, text("Status is 201, expected 200 to 299")));
+        assert_eq!(check(Assertion::StatusInRange { from: 400, to: 499 }), (false, text("Status is 201, expected 400 to 499")));
+    }
+
+    #[test]
+    fn headers() {
+        assert_eq!(check(Assertion::HeaderPresent { name: text("content-type") }), (true, text("Header \"content-type\" is present")));
+        assert_eq!(check(Assertion::HeaderPresent { name: text("ETag") }), (false, text("Header \"ETag\" is missing")));
+        let matches = |pattern: &str| Assertion::HeaderMatches { name: text("Content-Type"), pattern: text(pattern) };
+        assert_eq!(
+            check(matches("^application/json")),
+            (true, text("Header \"Content-Type\" is \"application/json; charset=utf-8\", expected to match \"^application/json\"")),
+        );
+        assert!(!check(matches("xml")).0);
+        let (passed, message) = check(matches("("));
+        assert!(!passed);
+        assert!(message.starts_with("Invalid pattern"));
+        assert_eq!(
+            check(Assertion::HeaderMatches { name: text("ETag"), pattern: text(".*") }),
+            (false, text("Header \"ETag\" is missing")),
+        );
+    }
+
+    #[test]
+    fn json_paths() {
+        let equals = |path: &str, value: &str| Assertion::JsonPathEquals { path: text(path), value: text(value) };
+        assert_eq!(check(equals("$.user.id", "7")), (true, text("$.user.id is [7], expected 7")));
+        // Values that aren't JSON are compared as strings
+        assert_eq!(check(equals("$.user.name", "Ada")), (true, text("$.user.name is [\"Ada\"], expected \"Ada\"")));
+        assert_eq!(check(equals("$.user.id", "8")), (false, text("$.user.id is [7], expected 8")));
+        assert!(check(equals("$.user.tags[*]", "\"admin\"")).0);
+
+        let exists = |path: &str| Assertion::JsonPathExists { path: text(path) };
+        assert_eq!(check(exists("$..name")), (true, text("$..name matched 1 values")));
+        assert_eq!(check(exists("$.user.email")), (false, text("$.user.email matched 0 values")));
+        assert!(!check(exists("$.user[")).0);
+    }
+
+    #[test]
+    fn body_and_time() {
+        assert_eq!(check(Assertion::BodyContains { text: text("Ada") }), (true, text("Body contains \"Ada\"")));
+        assert_eq!(check(Assertion::BodyContains { text: text("Bob") }), (false, text("Body doesn't contain \"Bob\"")));
+        assert_eq!(check(Assertion::ResponseTimeBelow { millis: 500 }), (true, text("Took 120 ms, expected below 500 ms")));
+        assert_eq!(check(Assertion::ResponseTimeBelow { millis: 100 }), (false, text("Took 120 ms, expected below 100 ms")));
+    }
+
+    #[test]
+    fn truncated_bodies() {
+        let mut response = response();
+        response.size *= 2;
+        let check = |assertion: Assertion| {
+            let result = assertion.evaluate(&response);
+            (result.passed, result.message)
+        };
+        let not_evaluated = (false, text("Not evaluated (body truncated)"));
+        assert_eq!(check(Assertion::JsonPathExists { path: text("$.user") }), not_evaluated);
+        assert_eq!(check(Assertion::JsonPathEquals { path: text("$.user.id"), value: text("7") }), not_evaluated);
+        // What is in the kept part is in the whole body too
+        assert_eq!(check(Assertion::BodyContains { text: text("Ada") }), (true, text("Body contains \"Ada\"")));
+        assert_eq!(check(Assertion::BodyContains { text: text("Bob") }), not_evaluated);
+        assert!(check(Assertion::StatusEquals { status: 201 }).0);
+    }
+}
@@ -0,0 +1,197 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Undo and redo for the edits of a request
+
+use crate::tabs::auth::AuthType;
+
+use super::{RequestData, RequestMethod, BodyType};
+
+/// Changes that come quicker than this are one undo step, so typing a word is undone at once
+const MERGE_SECONDS: f64 = 1.0;
+/// Typing without a pause still starts a new step after this long
+const MAX_STEP_SECONDS: f64 = 5.0;
+const MAX_UNDOS: usize = 100;
+/// Snapshots with large bodies are dropped early, so they don't use up the memory
+const MAX_UNDO_BYTES: usize = 32 * 1024 * 1024;
+
+/// Snapshots of the request data before each change
+#[derive(Default)]
+pub struct History {
+    /// Each snapshot with its size in bytes
+    undos: Vec<(RequestData, usize)>,
+    redos: Vec<RequestData>,
+    /// The data when it was last recorded
+    current: Option<RequestData>,
+    /// When the current step started and when it last changed
+    step: Option<(f64, f64)>,
+}
+
+impl History {
+    /// Call after every frame the request was drawn in. Typing is merged into one step,
+    /// but adding or removing rows and changing the method, auth or body type always starts a new one
+    pub fn record(&mut self, time: f64, data: &RequestData) {
+        let Some(current) = &self.current else {
+            self.current = Some(data.clone());
+            return;
+        };
+        if current == data {
+            return;
+        }
+        let merge = self.step.map_or(false, |(start, last)| time - last < MERGE_SECONDS && time - start < MAX_STEP_SECONDS)
+            && shape(current) == shape(data);
+        let previous = self.current.replace(data.clone());
+        match (merge, self.step) {
+            (true, Some((start, _))) => self.step = Some((start, time)),
+            _ => {
+                if let Some(previous) = previous {
+                    self.push_undo(previous);
+                }
+                self.step = Some((time, time));
+            },
+        }
+        self.redos.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undos.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redos.is_empty()
+    }
+
+    /// Puts the data back to before the last change. Returns false if there is nothing to undo
+    pub fn undo(&mut self, data: &mut RequestData) -> bool {
+        let Some((previous, _)) = self.undos.pop() else {
+            return false;
+        };
+        self.redos.push(std::mem::replace(data, previous));
+        self.restored(data);
+        true
+    }
+
+    pub fn redo(&mut self, data: &mut RequestData) -> bool {
+        let Some(next) = self.redos.pop() else {
+            return false;
+        };
+        let previous = std::mem::replace(data, next);
+        self.push_undo(previous);
+        self.restored(data);
+        true
+    }
+
+    fn restored(&mut self, data: &RequestData) {
+        self.current = Some(data.clone());
+        // The next edit is a new step, even if it comes right away
+        self.step = None;
+    }
+
+    fn push_undo(&mut self, data: RequestData) {
+        let size = serde_json::to_vec(&data).map_or(0, |json| json.len());
+        self.undos.push((data, size));
+        let mut total: usize = self.undos.iter().map(|(_, size)| size).sum();
+        while self.undos.len() > MAX_UNDOS || total > MAX_UNDO_BYTES && self.undos.len() > 1 {
+            total -= self.undos.remove(0).1;
+        }
+    }
+}
+
+/// What has to stay the same for two changes to be merged
+fn shape(data: &RequestData) -> (RequestMethod, usize, AuthType, BodyType, usize) {
+    (
+        data.method.clone(),
+        data.headers.len(),
+        data.selected_auth.clone(),
+        data.selected_body.clone(),
+        data.assertions.len(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typing_is_one_step() {
+        let mut history = History::default();
+        let mut data = RequestData::default();
+        history.record(0., &data);
+        for (i, c) in "https://example.com".chars().enumerate() {
+            data.url_string.push(c);
+            history.record(0.1 * i as f64, &data);
+        }
+
+        assert!(history.undo(&mut data));
+        assert_eq!(data.url_string, "");
+        assert!(!history.can_undo());
+        assert!(history.redo(&mut data));
+        assert_eq!(data.url_string, "https://example.com");
+    }
+
+    #[test]
+    fn removing_a_row_can_be_undone_right_away() {
+        let mut history = History::default();
+        let mut data = RequestData::default();
+        data.headers.push((String::from("Accept"), String::from("text/plain")));
+        history.record(0., &data);
+        data.headers[0].1.push_str(", text/html");
+        history.record(0.1, &data);
+        data.headers.clear();
+        history.record(0.2, &data);
+
+        assert!(history.undo(&mut data));
+        assert_eq!(data.headers, vec![(String::from("Accept"), String::from("text/plain, text/html"))]);
+        assert!(history.undo(&mut data));
+        assert_eq!(data.headers[0].1, "text/plain");
+    }
+
+    #[test]
+    fn new_edits_clear_the_redos() {
+        let mut history = History::default();
+        let mut data = RequestData::default();
+        history.record(0., &data);
+        data.name = String::from("First");
+        history.record(1., &data);
+        history.undo(&mut data);
+        data.name = String::from("Second");
+        history.record(3., &data);
+
+        assert!(!history.can_redo());
+        assert!(history.undo(&mut data));
+        assert_eq!(data.name, RequestData::default().name);
+    }
+
+    #[test]
+    fn long_typing_is_split_into_steps() {
+        let mut history = History::default();
+        let mut data = RequestData::default();
+        history.record(0., &data);
+        for i in 0..80 {
+            data.url_string.push('a');
+            history.record(0.1 * i as f64, &data);
+        }
+
+        assert!(history.undo(&mut data));
+        assert_eq!(data.url_string.len(), 50);
+        assert!(history.undo(&mut data));
+        assert_eq!(data.url_string, "");
+    }
+
+    #[test]
+    fn large_snapshots_are_dropped_first() {
+        let mut history = History::default();
+        let mut data = RequestData::default();
+        history.record(0., &data);
+        for (i, c) in "abcdef".chars().enumerate() {
+            data.url_string = c.to_string().repeat(MAX_UNDO_BYTES / 4);
+            history.record(2. * i as f64, &data);
+        }
+
+        // Three of them fit, the empty one and the first large ones are gone
+        assert_eq!(history.undos.len(), 3);
+        assert!(history.undo(&mut data));
+        assert!(data.url_string.starts_with('e'));
+    }
+}
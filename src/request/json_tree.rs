@@ -0,0 +1,169 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! A collapsible tree of a JSON response that can be narrowed down with a path
+
+use egui::{Color32, Id, Response, RichText, ScrollArea, TextEdit, Ui};
+use egui::collapsing_header::CollapsingState;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::highlight::{self, Kind};
+use crate::json_path;
+
+/// Longer arrays are split into groups of this many items, so opening one doesn't draw all of them
+const GROUP_SIZE: usize = 100;
+
+#[derive(Default)]
+pub struct JsonTree {
+    pub filter: String,
+    /// The parsed body and the run it is from
+    parsed: Option<(usize, Result<Value, String>)>,
+    filtered: Option<Filtered>,
+}
+
+/// What the filter selected, for the run and the filter it was computed with
+struct Filtered {
+    run: usize,
+    filter: String,
+    /// The paths and values that were found
+    found: Result<Vec<(String, Value)>, String>,
+}
+
+impl JsonTree {
+    /// Returns false if the body isn't JSON, so it can be shown as text instead.
+    /// `uuid` is the one of the request, so each request remembers which parts of its tree are open
+    pub fn render(&mut self, ui: &mut Ui, uuid: Uuid, body: &[u8], run: usize) -> bool {
+        if self.parsed.as_ref().map_or(true, |(parsed_run, _)| *parsed_run != run) {
+            self.parsed = Some((run, serde_json::from_slice(body).map_err(|e| e.to_string())));
+        }
+        let Some((_, Ok(value))) = &self.parsed else {
+            return false;
+        };
+
+        ui.horizontal(|ui| {
+            ui.label("Filter");
+            ui.add(TextEdit::singleline(&mut self.filter)
+                .hint_text("$..id, $.items[*].name or .items[].name")
+                .desired_width(f32::INFINITY));
+        });
+        let tree = Id::new(("json_tree", uuid));
+        let filter = self.filter.trim();
+        if filter.is_empty() {
+            ScrollArea::both().auto_shrink([false, false]).show(ui, |ui| {
+                render_value(ui, tree, "$", String::from("$"), value, true);
+            });
+            return true;
+        }
+
+        if !matches!(&self.filtered, Some(filtered) if filtered.run == run && filtered.filter == filter) {
+            let found = json_path::select_paths(value, filter)
+                .map(|found| found.into_iter().map(|(path, value)| (path, value.clone())).collect())
+                .map_err(|e| e.to_string());
+            self.filtered = Some(Filtered { run, filter: filter.to_owned(), found });
+        }
+        match self.filtered.as_ref().map(|filtered| &filtered.found) {
+            Some(Ok(found)) => {
+                ui.weak(match found.len() {
+                    1 => String::from("1 match"),
+                    n => format!("{} matches", n),
+                });
+                ScrollArea::both().auto_shrink([false, false]).show(ui, |ui| {
+                    for (path, value) in found {
+                        render_value(ui, tree, path, path.clone(), value, false);
+                    }
+                });
+            },
+            Some(Err(e)) => {
+                ui.colored_label(Color32::RED, e);
+            },
+            None => {},
+        }
+        true
+    }
+}
+
+/// One row of the tree. Objects and arrays can be opened to show what is in them
+fn render_value(ui: &mut Ui, tree: Id, key: &str, path: String, value: &Value, default_open: bool) {
+    let summary = match value {
+        Value::Object(map) => format!("{{…}} {}", count(map.len(), "key", "keys")),
+        Value::Array(array) => format!("[…] {}", count(array.len(), "item", "items")),
+        _ => {
+            let response = ui.horizontal(|ui| {
+                ui.label(RichText::new(format!("{}:", key)).strong());
+                ui.label(RichText::new(value.to_string()).monospace().color(value_color(ui, value)));
+            }).response;
+            copy_menu(response, &path, value);
+            return;
+        },
+    };
+    let id = tree.with(&path);
+    CollapsingState::load_with_default_open(ui.ctx(), id, default_open)
+        .show_header(ui, |ui| {
+            let response = ui.horizontal(|ui| {
+                ui.label(RichText::new(key).strong());
+                ui.weak(summary);
+            }).response;
+            copy_menu(response, &path, value);
+        })
+        .body(|ui| match value {
+            Value::Object(map) => {
+                for (key, child) in map {
+                    render_value(ui, tree, key, json_path::child_path(&path, key), child, false);
+                }
+            },
+            Value::Array(array) if array.len() > GROUP_SIZE => {
+                for start in (0..array.len()).step_by(GROUP_SIZE) {
+                    let end = (start + GROUP_SIZE).min(array.len());
+                    CollapsingState::load_with_default_open(ui.ctx(), id.with(start), false)
+                        .show_header(ui, |ui| ui.weak(format!("[{} … {}]", start, end - 1)))
+                        .body(|ui| render_items(ui, tree, &path, &array[start..end], start));
+                }
+            },
+            Value::Array(array) => render_items(ui, tree, &path, array, 0),
+            _ => {},
+        });
+}
+
+fn render_items(ui: &mut Ui, tree: Id, path: &str, items: &[Value], offset: usize) {
+    for (i, item) in items.iter().enumerate() {
+        let index = offset + i;
+        render_value(ui, tree, &index.to_string(), json_path::index_path(path, index), item, false);
+    }
+}
+
+fn copy_menu(response: Response, path: &str, value: &Value) {
+    response.on_hover_text(path).context_menu(|ui| {
+        if ui.button("Copy Path").clicked() {
+            ui.output_mut(|o| o.copied_text = path.to_owned());
+            ui.close_menu();
+        }
+        if ui.button("Copy Value").clicked() {
+            let text = match value {
+                Value::String(s) => s.clone(),
+                _ => serde_json::to_string_pretty(value).unwrap_or_default(),
+            };
+            ui.output_mut(|o| o.copied_text = text);
+            ui.close_menu();
+        }
+    });
+}
+
+/// The same colors as in the highlighted text view
+fn value_color(ui: &Ui, value: &Value) -> Color32 {
+    let kind = match value {
+        Value::String(_) => Kind::String,
+        Value::Number(_) => Kind::Number,
+        Value::Bool(_) => Kind::Keyword,
+        _ => Kind::Comment,
+    };
+    highlight::color(ui, kind)
+}
+
+fn count(n: usize, one: &str, many: &str) -> String {
+    match n {
+        1 => format!("1 {}", one),
+        n => format!("{} {}", n, many),
+    }
+}
@@ -2,47 +2,60 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-mod tabs;
+pub mod tabs;
+pub mod assertions;
+mod history;
+mod json_tree;
+mod viewer;
 
 use std::collections::BTreeMap;
 use std::collections::hash_map::DefaultHasher;
 use std::fmt::Debug;
 use std::hash::{Hasher, Hash};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
 use std::{cell::RefCell, rc::Rc};
 
-use egui::{Ui, Layout, Align, TextEdit, ScrollArea, TopBottomPanel};
+use egui::{Ui, Layout, Align, TextEdit, TopBottomPanel, Color32, RichText};
 
-use reqwest::blocking::{Client, Response};
-use reqwest::Method;
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, StatusCode};
 use serde::{Serialize, Deserialize};
 
 use uuid::Uuid;
 
 use poll_promise::Promise;
 
+use crate::engine::{self, Outcome, Progress, ResponseData};
+use crate::jwt;
 use crate::tabs::auth::AuthData;
 use crate::tabs::Tab;
-use crate::{tabs::auth::AuthType, collection::CollectionData};
+use crate::{tabs::auth::AuthType, collection::{CollectionData, FolderData}};
 use crate::request::tabs::auth_tab::AuthorizationTab;
 
 use self::tabs::body_tab::{BodyType, BodyData, BodyTab};
 use self::tabs::headers_tab::HeadersTab;
 use self::tabs::parameters_tab::ParametersTab;
+use self::assertions::Assertion;
+use self::tabs::script_tab::ScriptTab;
+use self::tabs::tests_tab::TestsTab;
+use self::history::History;
+use self::viewer::Viewer;
 
-#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[derive(Debug, Default, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
 pub enum RequestMethod {
     Options,
     Head,
+    #[default]
     Get,
     Post,
     Put,
     Patch
 }
 
-impl Into<reqwest::Method> for RequestMethod {
-    fn into(self) -> reqwest::Method {
-        match self {
+impl From<RequestMethod> for reqwest::Method {
+    fn from(method: RequestMethod) -> Self {
+        match method {
             RequestMethod::Options => Method::OPTIONS,
             RequestMethod::Head => Method::HEAD,
             RequestMethod::Get => Method::GET,
@@ -53,22 +66,32 @@ impl Into<reqwest::Method> for RequestMethod {
     }
 }
 
-impl ToString for RequestMethod {
-    fn to_string(&self) -> String {
-        match self {
+impl std::str::FromStr for RequestMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "OPTIONS" => Ok(Self::Options),
+            "HEAD" => Ok(Self::Head),
+            "GET" => Ok(Self::Get),
+            "POST" => Ok(Self::Post),
+            "PUT" => Ok(Self::Put),
+            "PATCH" => Ok(Self::Patch),
+            _ => Err(anyhow::anyhow!("Unknown request method \"{}\"", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for RequestMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
             Self::Options => "OPTIONS",
             Self::Head => "HEAD",
             Self::Get => "GET",
             Self::Post => "POST",
             Self::Put => "PUT",
             Self::Patch => "PATCH",
-        }.to_string()
-    }
-}
-
-impl Default for RequestMethod {
-    fn default() -> Self {
-        Self::Get
+        })
     }
 }
 
@@ -78,16 +101,8 @@ pub enum RequestTab {
     Authorization,
     Headers,
     Body,
-}
-
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
-pub enum RequestResult {
-    None,
-    Pending,
-    Some {
-        body: String,
-        headers: String
-    }
+    Script,
+    Tests,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
@@ -103,6 +118,11 @@ pub struct RequestData {
     
     pub body: BTreeMap<BodyType, BodyData>,
     pub selected_body: BodyType,
+    
+    #[serde(default)]
+    pub pre_request_script: String,
+    #[serde(default)]
+    pub assertions: Vec<Assertion>,
 }
 
 impl Default for RequestData {
@@ -116,10 +136,24 @@ impl Default for RequestData {
             selected_auth: Default::default(),
             body: Default::default(),
             selected_body: Default::default(),
+            pre_request_script: String::new(),
+            assertions: vec![],
         }
     }
 }
 
+/// How many responses of a request are kept to compare them
+const RECORDED_RESPONSES: usize = 10;
+
+/// A response that was received earlier
+pub struct RecordedResponse {
+    /// Counts the runs of the request, starting at 1
+    pub run: usize,
+    /// The run and the environment it was sent with
+    pub label: String,
+    pub response: ResponseData,
+}
+
 // TODO: Move name to RequestData and have RequestData.changed = true if the data has been modified since the last save
 // TODO: Remove the Rc<RefCell<RequestData>> and pass the RequestData into the Tabs on render
 #[derive(Serialize, Deserialize)]
@@ -127,21 +161,60 @@ pub struct Request {
     pub uuid: Uuid,
     
     request_data: RequestData,
-    // TODO: This is probably also not serialized correctly. Find an answer! Maybe pass in the data when rendering the request
+    /// Shared with the collection. Not serialized, the collection hands it out again when it is loaded
+    #[serde(skip)]
     collection_data: Rc<RefCell<CollectionData>>,
+    /// The folders the request is in, outermost first
+    #[serde(skip)]
+    folder_data: Vec<Rc<RefCell<FolderData>>>,
     
+    /// The console of the scripts and the outcome of the request
+    #[serde(skip)]
+    promise: Option<Promise<(Vec<String>, anyhow::Result<Outcome>)>>,
     #[serde(skip)]
-    promise: Option<Promise<Result<Response, reqwest::Error>>>,
+    console: Vec<String>,
     
     tab: RequestTab,
     
     auth_tab: AuthorizationTab,
     params_tab: ParametersTab,
     headers_tab: HeadersTab,
+    /// Has nothing worth saving, only the find bar
+    #[serde(skip, default = "BodyTab::new")]
     body_tab: BodyTab,
+    #[serde(default = "ScriptTab::new")]
+    script_tab: ScriptTab,
+    #[serde(default = "TestsTab::new")]
+    tests_tab: TestsTab,
     
     pub wants_save: bool,
     pub saved_data_hash: Option<u64>,
+    /// Sends the request the next time it is drawn, like clicking "Send"
+    #[serde(skip)]
+    pub wants_send: bool,
+    #[serde(skip)]
+    history: History,
+    /// The last responses, oldest first
+    #[serde(skip)]
+    responses: Vec<RecordedResponse>,
+    /// The label of the response that is still on its way
+    #[serde(skip)]
+    pending_label: Option<String>,
+    #[serde(skip)]
+    runs: usize,
+    /// Asks the app to compare the last responses
+    #[serde(skip)]
+    pub wants_compare: bool,
+    /// Streams the body into a file instead of keeping it in memory
+    #[serde(default)]
+    download: bool,
+    #[serde(skip)]
+    progress: Option<Arc<Progress>>,
+    /// The file the last response was downloaded into
+    #[serde(skip)]
+    downloaded_to: Option<PathBuf>,
+    #[serde(skip)]
+    viewer: Viewer,
 }
 
 impl Debug for Request {
@@ -166,14 +239,28 @@ impl Clone for Request {
             // We don't want to clone the reference, but the data
             request_data: self.request_data.clone(),
             collection_data: Rc::clone(&self.collection_data),
+            folder_data: self.folder_data.clone(),
             promise: None,
+            console: vec![],
             tab: self.tab.clone(),
             auth_tab: self.auth_tab.clone(),
             params_tab: self.params_tab.clone(),
             headers_tab: self.headers_tab.clone(),
             body_tab: self.body_tab.clone(),
+            script_tab: self.script_tab.clone(),
+            tests_tab: self.tests_tab.clone(),
             wants_save: false,
-            saved_data_hash: None,
+            saved_data_hash: self.saved_data_hash,
+            wants_send: false,
+            history: Default::default(),
+            responses: vec![],
+            pending_label: None,
+            runs: 0,
+            wants_compare: false,
+            download: self.download,
+            progress: None,
+            downloaded_to: None,
+            viewer: Viewer::default(),
         }
     }
 }
@@ -185,20 +272,72 @@ impl Request {
             uuid: Uuid::new_v4(),
             request_data: Default::default(),
             collection_data,
+            folder_data: vec![],
             promise: Default::default(),
+            console: vec![],
             tab: RequestTab::Parameters,
             auth_tab: AuthorizationTab::new(),
             params_tab: ParametersTab::new(),
             headers_tab: HeadersTab::new(),
             body_tab: BodyTab::new(),
+            script_tab: ScriptTab::new(),
+            tests_tab: TestsTab::new(),
             wants_save: false,
             saved_data_hash: None,
+            wants_send: false,
+            history: Default::default(),
+            responses: vec![],
+            pending_label: None,
+            runs: 0,
+            wants_compare: false,
+            download: false,
+            progress: None,
+            downloaded_to: None,
+            viewer: Viewer::default(),
+        }
+    }
+    
+    /// A request that is not in any collection yet. It uses empty collection data until it is saved to one
+    pub fn scratch() -> Self {
+        Self::with_data(Default::default(), Default::default())
+    }
+    
+    /// Creates a request from saved data, so it starts out unchanged
+    pub fn with_data(request_data: RequestData, collection_data: Rc<RefCell<CollectionData>>) -> Self {
+        let mut request = Self::new(collection_data);
+        request.request_data = request_data;
+        request.params_tab.url_to_params(&mut request.request_data);
+        request.saved_data_hash = Some(request.data_hash());
+        request
+    }
+    
+    pub fn set_scope(&mut self, collection_data: Rc<RefCell<CollectionData>>, folder_data: Vec<Rc<RefCell<FolderData>>>) {
+        self.collection_data = collection_data;
+        self.folder_data = folder_data;
+    }
+    
+    /// The collection data with the settings of the folders applied
+    fn scoped_data(&self) -> CollectionData {
+        let folders: Vec<_> = self.folder_data.iter().map(|f| f.borrow()).collect();
+        self.collection_data.borrow().scoped(folders.iter().map(|f| &**f))
+    }
+    
+    /// Changes when the auth settings of the request or anything in its scope change
+    fn scope_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (&self.request_data.auth, &self.request_data.selected_auth).hash(&mut hasher);
+        self.collection_data.borrow().hash(&mut hasher);
+        for folder in &self.folder_data {
+            folder.borrow().hash(&mut hasher);
         }
+        hasher.finish()
     }
     
     pub fn duplicate(&self) -> Self {
         let mut cloned = self.clone();
         cloned.uuid = Uuid::new_v4();
+        // The copy isn't saved anywhere yet
+        cloned.saved_data_hash = None;
         cloned
     }
     
@@ -212,6 +351,12 @@ impl Request {
                     if ui.button("Save").clicked() {
                         self.wants_save = true;
                     }
+                    if ui.add_enabled(self.history.can_undo(), egui::Button::new("⟲")).on_hover_text("Undo").clicked() {
+                        self.undo();
+                    }
+                    if ui.add_enabled(self.history.can_redo(), egui::Button::new("⟳")).on_hover_text("Redo").clicked() {
+                        self.redo();
+                    }
                 });
             });
             ui.add_space(10.);
@@ -230,9 +375,11 @@ impl Request {
                     });
                 ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                     let resp = ui.button("Send");
-                    if resp.clicked() {
+                    if resp.clicked() || std::mem::take(&mut self.wants_send) {
                         self.send_request(&resp.ctx);
                     }
+                    ui.checkbox(&mut self.download, "Download")
+                        .on_hover_text("Stream the body into a file instead of keeping it in memory");
                     let host = &mut self.request_data.url_string;
                     let host_bar = egui::TextEdit::singleline(host)
                                                             .hint_text("https://...")
@@ -246,6 +393,8 @@ impl Request {
                 ui.selectable_value(&mut self.tab, RequestTab::Authorization, "Authorization");
                 ui.selectable_value(&mut self.tab, RequestTab::Headers, "Headers");
                 ui.selectable_value(&mut self.tab, RequestTab::Body, "Body");
+                ui.selectable_value(&mut self.tab, RequestTab::Script, "Pre-request Script");
+                ui.selectable_value(&mut self.tab, RequestTab::Tests, "Tests");
             });
             ui.add_space(5.);
             
@@ -257,34 +406,76 @@ impl Request {
                     self.auth_tab.render(ui, &mut self.request_data);
                 },
                 RequestTab::Headers => {
+                    // Signing a JWT is too slow to do every frame
+                    let scope_hash = self.scope_hash();
+                    if self.headers_tab.computed_for != Some(scope_hash) {
+                        let scoped = self.scoped_data();
+                        self.headers_tab.auth_header = engine::auth_header(&self.request_data, &scoped)
+                            .map_err(|e| format!("{:#}", e))
+                            .transpose();
+                        self.headers_tab.inherited_headers = scoped.headers;
+                        self.headers_tab.computed_for = Some(scope_hash);
+                    }
                     self.headers_tab.render(ui, &mut self.request_data);
                 },
                 RequestTab::Body => {
                     self.body_tab.render(ui, &mut self.request_data);
+                },
+                RequestTab::Script => {
+                    self.script_tab.render(ui, &mut self.request_data);
+                },
+                RequestTab::Tests => {
+                    self.tests_tab.render(ui, &mut self.request_data);
                 }
             }
             
             ui.add_space(10.);
         });
-        if let Some(promise) = &mut self.promise {
-            if let Some(result) = promise.ready() {
-                let mut response_text = match result {
-                    Ok(s) => (*s).clone().text().unwrap_or_default(),
-                    Err(s) => s.to_string(),
-                };
-                let textedit = TextEdit::multiline(&mut response_text)
-                    .frame(true)
-                    .code_editor();
-                ScrollArea::horizontal().show(ui, |ui| {
-                    ui.add_sized(ui.available_size(), textedit);
-                });
+        if !self.console.is_empty() {
+            egui::CollapsingHeader::new(format!("Console ({})", self.console.len())).show(ui, |ui| {
+                for line in &self.console {
+                    ui.monospace(line);
+                }
+            });
+        }
+        if let Some(promise) = &self.promise {
+            if let Some((console, result)) = promise.ready() {
+                // Only once, when the response arrives
+                if let Some(label) = self.pending_label.take() {
+                    self.console = console.clone();
+                    if let Ok(outcome) = result {
+                        self.responses.push(RecordedResponse { run: self.runs, label, response: outcome.response.clone() });
+                        if self.responses.len() > RECORDED_RESPONSES {
+                            self.responses.remove(0);
+                        }
+                        self.store_variables(outcome.variables.clone());
+                    }
+                }
+                match result {
+                    Ok(outcome) => {
+                        self.wants_compare |= render_outcome(ui, &mut self.viewer, outcome, self.runs, self.downloaded_to.as_deref(), &self.request_data.name);
+                        self.viewer.render(ui, self.uuid, &outcome.response, self.runs);
+                    },
+                    Err(e) => {
+                        let mut error_text = format!("{:#}", e);
+                        let textedit = TextEdit::multiline(&mut error_text)
+                            .frame(true)
+                            .code_editor();
+                        ui.add_sized(ui.available_size(), textedit);
+                    },
+                }
             } else {
                 // TODO: Loading screen
                 ui.horizontal_centered(|ui| {
                     ui.vertical_centered(|ui| {
                         ui.label("Waiting for a Response...");
                         ui.add_space(5.);
-                        ui.spinner();
+                        match &self.progress {
+                            Some(progress) => render_progress(ui, progress),
+                            None => {
+                                ui.spinner();
+                            },
+                        }
                     })
                 });
             }
@@ -292,12 +483,47 @@ impl Request {
         if uri_changed {
             self.params_tab.url_to_params(&mut self.request_data);
         }
+        self.history.record(ui.input(|i| i.time), &self.request_data);
+    }
+    
+    /// Puts back edits that were not saved, e.g. after a crash. The request stays marked as changed
+    pub fn restore_draft(&mut self, request_data: RequestData) {
+        self.request_data = request_data;
+        self.params_tab.url_to_params(&mut self.request_data);
+    }
+    
+    /// Opens the find bar of the body while it is being edited, or else the one of the response
+    pub fn find(&mut self) {
+        match self.tab == RequestTab::Body && self.body_tab.focused {
+            true => self.body_tab.find.open(),
+            false => self.viewer.find.open(),
+        }
+    }
+    
+    pub fn undo(&mut self) {
+        if self.history.undo(&mut self.request_data) {
+            self.params_tab.url_to_params(&mut self.request_data);
+        }
+    }
+    
+    pub fn redo(&mut self) {
+        if self.history.redo(&mut self.request_data) {
+            self.params_tab.url_to_params(&mut self.request_data);
+        }
     }
     
     pub fn name(&self) -> String {
         self.request_data.name.clone()
     }
     
+    pub fn responses(&self) -> &[RecordedResponse] {
+        &self.responses
+    }
+    
+    pub fn request_data(&self) -> &RequestData {
+        &self.request_data
+    }
+    
     /// Checks if we want to save and marks the saved data as unchanged if we do
     pub fn do_save(&mut self) -> bool {
         // Return early if we don't actually want to save
@@ -305,70 +531,178 @@ impl Request {
             return false;
         }
         
-        let mut hasher = DefaultHasher::new();
-        self.request_data.hash(&mut hasher);
-        self.saved_data_hash = Some(hasher.finish());
+        self.saved_data_hash = Some(self.data_hash());
         
         true
     }
     
+    fn data_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.request_data.hash(&mut hasher);
+        hasher.finish()
+    }
+    
     pub fn changed_since_save(&self) -> bool {
         let Some(saved_hash) = self.saved_data_hash else {
             return true;
         };
         
-        let mut hasher = DefaultHasher::new();
-        self.request_data.hash(&mut hasher);
-        
-        saved_hash != hasher.finish()
+        saved_hash != self.data_hash()
     }
     
     fn send_request(&mut self, ctx: &egui::Context) {
-        let mut headers = BTreeMap::new();
-        for (key, value) in self.request_data.headers.clone() {
-            headers.insert(key, value);
-        }
+        self.console.clear();
+        let download_to = match self.download {
+            true => match rfd::FileDialog::new().set_file_name(&file_name(&self.request_data.url_string)).save_file() {
+                Some(path) => Some(path),
+                None => return,
+            },
+            false => None,
+        };
+        self.progress = download_to.as_ref().map(|_| Arc::default());
+        self.downloaded_to = download_to.clone();
         
+        let mut scoped = self.scoped_data();
+        self.runs += 1;
+        let environment = scoped.selected_environment.as_deref().unwrap_or("No Environment");
+        self.pending_label = Some(format!("Run {} · {}", self.runs, environment));
+
         let ctx = ctx.clone();
         let (sender, promise) = Promise::new();
-        let body = self.request_data.body.get(&self.request_data.selected_body).map(|b| b.clone().to_body()).unwrap_or_default();
+        let request_data = self.request_data.clone();
+        let progress = self.progress.clone();
+        
+        // The scripts and signing a JWT can take a while, so they run with the request instead of on the UI thread
+        std::thread::spawn(move|| {
+            let mut console = vec![];
+            let outcome = engine::prepare(&request_data, &scoped, &mut console).and_then(|prepared| {
+                scoped.store_variables(prepared.variables.clone());
+                let client = engine::client()?;
+                let request = engine::build_request(&client, &prepared.request_data, &scoped)?;
+                let response = match (&download_to, &progress) {
+                    (Some(path), Some(progress)) => engine::download(&client, request, path, progress),
+                    _ => engine::execute(&client, request),
+                }?;
+                Ok(Outcome {
+                    test_results: assertions::evaluate_all(&prepared.request_data.assertions, &response),
+                    response,
+                    variables: prepared.variables,
+                })
+            });
 
-        let client = Client::builder()
-            .danger_accept_invalid_certs(true)
-            .build()
-            .unwrap();
+            sender.send((console, outcome));
+            ctx.request_repaint();
+        });
 
-        let mut headers = HeaderMap::new();
+        self.promise = Some(promise);
+    }
 
-        for (key, value) in self.request_data.headers.clone() {
-            // TODO: This needs better error handling
-            let header_name = HeaderName::from_bytes(key.as_bytes()).unwrap();
-            let header_value = HeaderValue::from_str(&value).unwrap();
-            headers.append(header_name, header_value);
-        }
+    /// Keeps the variables set by the scripts for the following requests
+    fn store_variables(&self, variables: BTreeMap<String, String>) {
+        let mut scoped = self.scoped_data();
+        scoped.store_variables(variables);
+        let mut folders: Vec<_> = self.folder_data.iter().map(|f| f.borrow_mut()).collect();
+        self.collection_data.borrow_mut().unscope(folders.iter_mut().map(|f| &mut **f), scoped);
+    }
+}
 
-        let request_builder = client
-            .request(self.request_data.method.clone().into(), self.request_data.url_string.clone())
-            .headers(headers)
-            .body(body);
+/// The name of the last part of the URL, to suggest it when saving the body
+fn file_name(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    path.rsplit('/').next()
+        .filter(|name| !name.is_empty() && !name.contains(':'))
+        .unwrap_or("response")
+        .to_owned()
+}
 
-        let request = request_builder.build();
-        
-        std::thread::spawn(move|| {
-            let request = match request {
-                Ok(r) => r,
-                Err(e) => {
-                    sender.send(Err(e));
-                    return;
-                }
-            };
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} bytes", bytes),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1024.),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.),
+    }
+}
 
-            sender.send(client.execute(request));
-            ctx.request_repaint();
-            
+fn render_progress(ui: &mut Ui, progress: &Progress) {
+    let received = progress.received.load(Ordering::Relaxed);
+    let total = progress.total.load(Ordering::Relaxed);
+    let bar = match total {
+        0 => egui::ProgressBar::new(0.).text(format_size(received)).animate(true),
+        _ => egui::ProgressBar::new(received as f32 / total as f32)
+            .text(format!("{} of {}", format_size(received), format_size(total))),
+    };
+    ui.add(bar.desired_width(300.));
+    // The download doesn't ask for repaints, so the bar would only move when the mouse does
+    ui.ctx().request_repaint_after(std::time::Duration::from_millis(100));
+}
+
+fn save_body(response: &ResponseData, name: &str) {
+    let Some(path) = rfd::FileDialog::new().set_file_name(name).save_file() else {
+        return;
+    };
+    if let Err(e) = std::fs::write(&path, &response.body) {
+        log::error!("Saving the response to {} failed: {}", path.display(), e);
+    }
+}
+
+/// Returns true if the user wants to compare the response with others
+fn render_outcome(ui: &mut Ui, viewer: &mut Viewer, outcome: &Outcome, run: usize, downloaded_to: Option<&Path>, name: &str) -> bool {
+    let response = &outcome.response;
+    let mut compare = false;
+    ui.horizontal(|ui| {
+        let reason = StatusCode::from_u16(response.status).ok().and_then(|s| s.canonical_reason()).unwrap_or_default();
+        let color = match response.status {
+            200..=299 => Color32::GREEN,
+            300..=399 => Color32::YELLOW,
+            _ => Color32::RED,
+        };
+        ui.colored_label(color, RichText::new(format!("{} {}", response.status, reason)).strong());
+        ui.label(format!("{} ms", response.duration.as_millis()));
+        ui.label(format_size(response.size));
+        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+            compare = ui.button("Compare…").on_hover_text("Compare with an earlier response").clicked();
+            viewer.render_picker(ui, response);
+            // A downloaded body is already in its file, and only its start is in memory
+            if !response.is_truncated() && ui.button("Save…").on_hover_text("Save the body to a file").clicked() {
+                save_body(response, name);
+            }
         });
+    });
+    if let Some(path) = downloaded_to {
+        ui.label(format!("Saved to {}", path.display()));
+    }
 
+    if !outcome.test_results.is_empty() {
+        let passed = outcome.test_results.iter().filter(|r| r.passed).count();
+        egui::CollapsingHeader::new(format!("Tests: {}/{} passed", passed, outcome.test_results.len()))
+            .default_open(true)
+            .show(ui, |ui| {
+                for result in &outcome.test_results {
+                    match result.passed {
+                        true => ui.colored_label(Color32::GREEN, format!("✔ {}", result.message)),
+                        false => ui.colored_label(Color32::RED, format!("✘ {}", result.message)),
+                    };
+                }
+            });
+    }
 
-        self.promise = Some(promise);
+    egui::CollapsingHeader::new(format!("Headers ({})", response.headers.len())).show(ui, |ui| {
+        for (key, value) in &response.headers {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(key).strong());
+                ui.label(value);
+            });
+        }
+    });
+
+    let tokens = viewer.tokens(response, run);
+    if !tokens.is_empty() {
+        egui::CollapsingHeader::new(format!("Tokens in Response ({})", tokens.len())).show(ui, |ui| {
+            for token in tokens {
+                ui.group(|ui| jwt::render_inspector(ui, token));
+            }
+        });
     }
-}
\ No newline at end of file
+
+    compare
+}
@@ -2,11 +2,11 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::{rc::Rc, cell::RefCell, default};
-
 use egui::{Ui, TextEdit};
 use serde::{Serialize, Deserialize};
 
+use crate::find::FindBar;
+use crate::highlight::{self, Language};
 use crate::{request::RequestData, tabs::Tab};
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -35,12 +35,19 @@ impl BodyData {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
-pub struct BodyTab;
+#[derive(Clone, PartialEq, Eq)]
+pub struct BodyTab {
+    pub find: FindBar,
+    /// True if the editor or its find bar had the focus
+    pub focused: bool,
+}
 
 impl BodyTab {
     pub fn new() -> Self  {
-        Self {}
+        Self {
+            find: FindBar::default(),
+            focused: false,
+        }
     }
 }
 impl Tab for BodyTab {
@@ -58,17 +65,30 @@ impl Tab for BodyTab {
         match request_data.selected_body {
             BodyType::None => {},
             BodyType::Raw => {
+                let content_type = request_data.headers.iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case("Content-Type"))
+                    .map(|(_, value)| value.clone());
                 let body_data = request_data.body.entry(BodyType::Raw)
                     .or_insert(BodyData::Raw { data: String::new() });
                 let BodyData::Raw { data } = body_data else {
                     panic!("Someone inserted a wrong body type into the request body value");
                 };
-                let text_edit = TextEdit::multiline(data).code_editor();
-                ui.horizontal(|ui| {
-                    ui.add_sized(ui.available_size(), text_edit);
-                });
+                let language = Language::detect(content_type.as_deref(), data);
+                self.find.render(ui, data);
+                let output = {
+                    let find = &self.find;
+                    let mut layouter = |ui: &Ui, text: &str, wrap_width: f32| {
+                        find.layout(ui, highlight::job(ui, text, language, true), wrap_width)
+                    };
+                    let text_edit = TextEdit::multiline(data).code_editor().layouter(&mut layouter);
+                    ui.horizontal(|ui| text_edit.min_size(ui.available_size()).show(ui)).inner
+                };
+                self.focused = output.response.has_focus() || self.find.has_focus;
+                self.find.scroll_to_match(ui, &output);
+            },
+            BodyType::Binary => {
+                ui.weak("Binary bodies are not supported yet. The request is sent without a body.");
             },
-            BodyType::Binary => todo!(),
         }
     }
 }
\ No newline at end of file
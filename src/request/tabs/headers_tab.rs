@@ -2,9 +2,7 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::{rc::Rc, cell::{RefCell, RefMut}};
-
-use egui::{Ui, Button};
+use egui::{Ui, Button, TextEdit};
 use egui_extras::{TableBuilder, Column};
 
 use serde::{Serialize, Deserialize};
@@ -15,12 +13,25 @@ use crate::{request::RequestData, tabs::Tab};
 #[derive(Serialize, Deserialize, Clone)]
 pub struct HeadersTab {
     new_header: (String, String),
+    
+    /// The `Authorization` header computed from the auth settings. It is only added when sending
+    #[serde(skip)]
+    pub auth_header: Option<Result<String, String>>,
+    /// The hash of the auth settings and the scope the header was computed from
+    #[serde(skip)]
+    pub computed_for: Option<u64>,
+    /// Headers of the collection and folders. Only shown if the request doesn't set them itself
+    #[serde(skip)]
+    pub inherited_headers: Vec<(String, String)>,
 }
 
 impl HeadersTab {
     pub fn new() -> Self {
         Self {
-            new_header: (String::new(), String::new())
+            new_header: (String::new(), String::new()),
+            auth_header: None,
+            computed_for: None,
+            inherited_headers: vec![],
         }
     }
     
@@ -28,7 +39,7 @@ impl HeadersTab {
         if self.new_header.0.is_empty() && self.new_header.1.is_empty() {
             return false;
         }
-        let param = std::mem::replace(&mut self.new_header, (String::new(), String::new()));
+        let param = std::mem::take(&mut self.new_header);
         
         let headers = &mut request_data.headers;
         headers.push(param);
@@ -47,6 +58,42 @@ impl Tab for HeadersTab {
             .column(Column::initial(128.).resizable(true))
             .column(Column::remainder())
             .body(|mut body| {
+                if let Some(auth_header) = &self.auth_header {
+                    body.row(24., |mut row| {
+                        row.col(|ui| {
+                            ui.add_enabled(false, TextEdit::singleline(&mut "Authorization"));
+                        });
+                        row.col(|ui| {
+                            match auth_header {
+                                Ok(value) => {
+                                    ui.add_enabled(false, TextEdit::singleline(&mut value.as_str()));
+                                },
+                                Err(e) => {
+                                    ui.colored_label(egui::Color32::RED, e);
+                                },
+                            }
+                        });
+                        row.col(|ui| {
+                            ui.weak("auto").on_hover_text("Set from the Authorization tab when the request is sent");
+                        });
+                    });
+                }
+                for (key, value) in &self.inherited_headers {
+                    if request_data.headers.iter().any(|(own, _)| own.eq_ignore_ascii_case(key)) {
+                        continue;
+                    }
+                    body.row(24., |mut row| {
+                        row.col(|ui| {
+                            ui.add_enabled(false, TextEdit::singleline(&mut key.as_str()));
+                        });
+                        row.col(|ui| {
+                            ui.add_enabled(false, TextEdit::singleline(&mut value.as_str()));
+                        });
+                        row.col(|ui| {
+                            ui.weak("inherited").on_hover_text("Set on the collection or a folder. Add the header here to override it");
+                        });
+                    });
+                }
                 {
                     let headers = &mut request_data.headers;
                     for (i, (key,value)) in headers.iter_mut().enumerate() {
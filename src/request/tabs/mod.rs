@@ -5,4 +5,6 @@
 pub mod auth_tab;
 pub mod parameters_tab;
 pub mod headers_tab;
-pub mod body_tab;
\ No newline at end of file
+pub mod body_tab;
+pub mod script_tab;
+pub mod tests_tab;
\ No newline at end of file
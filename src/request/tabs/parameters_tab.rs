@@ -2,17 +2,14 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::{rc::Rc, cell::RefCell};
-
 use egui::{Ui, Button};
 use egui_extras::{TableBuilder, Column};
 
 use serde::{Serialize, Deserialize};
 
-use url::Url;
 use percent_encoding;
 
-use crate::{request::{RequestData, self}, tabs::Tab};
+use crate::{request::RequestData, tabs::Tab};
 
 
 // TODO: Paremeters broke
@@ -34,7 +31,7 @@ impl ParametersTab {
         if self.new_param.0.is_empty() && self.new_param.1.is_empty() {
             return false;
         }
-        let param = std::mem::replace(&mut self.new_param, (String::new(), String::new()));
+        let param = std::mem::take(&mut self.new_param);
         self.parameters.push(param);
         true
     }
@@ -53,7 +50,7 @@ impl ParametersTab {
         }
     
         let url = &mut request_data.url_string;
-        let base_url = get_base_url(&url);
+        let base_url = get_base_url(url);
         println!("Url Parts: {}", url_parts.len());
         let new_url = if url_parts.is_empty() {
             base_url.to_string()
@@ -67,7 +64,7 @@ impl ParametersTab {
     pub fn url_to_params(&mut self, request_data: &mut RequestData) {
         let url = &request_data.url_string;
         self.parameters = url::Url::options()
-                    .parse(&url)
+                    .parse(url)
                     .map(|u| {
                         u.query_pairs()
                             .map(|(k, v)| (k.to_string(), v.to_string()))
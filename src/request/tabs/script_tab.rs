@@ -0,0 +1,33 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use egui::{Ui, TextEdit};
+use serde::{Serialize, Deserialize};
+
+use crate::{request::RequestData, tabs::Tab};
+
+pub const SCRIPT_HELP: &str = "Runs before the request is sent. Change `url`, `method`, `headers`, `body` and `variables`, \
+write to the console with `log` and use `timestamp()`, `timestamp_ms()`, `uuid()`, `base64(s)`, `sha256(s)` and `hmac_sha256(key, message)`.";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScriptTab;
+
+impl ScriptTab {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Tab for ScriptTab {
+    type T = RequestData;
+
+    fn render(&mut self, ui: &mut Ui, request_data: &mut Self::T) {
+        ui.weak(SCRIPT_HELP);
+        ui.add_space(5.);
+        let text_edit = TextEdit::multiline(&mut request_data.pre_request_script)
+            .code_editor()
+            .hint_text("headers[\"X-Timestamp\"] = timestamp().to_string();");
+        ui.add_sized(ui.available_size(), text_edit);
+    }
+}
@@ -0,0 +1,91 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use egui::{Ui, TextEdit, DragValue};
+use serde::{Serialize, Deserialize};
+
+use crate::{request::{RequestData, assertions::Assertion}, tabs::Tab};
+
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TestsTab;
+
+impl TestsTab {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+fn render_assertion(ui: &mut Ui, assertion: &mut Assertion) {
+    match assertion {
+        Assertion::StatusEquals { status } => {
+            ui.add(DragValue::new(status).clamp_range(100..=599));
+        },
+        Assertion::StatusInRange { from, to } => {
+            ui.add(DragValue::new(from).clamp_range(100..=599));
+            ui.label("to");
+            ui.add(DragValue::new(to).clamp_range(100..=599));
+        },
+        Assertion::HeaderPresent { name } => {
+            ui.add(TextEdit::singleline(name).hint_text("Header"));
+        },
+        Assertion::HeaderMatches { name, pattern } => {
+            ui.add(TextEdit::singleline(name).hint_text("Header").desired_width(128.));
+            ui.add(TextEdit::singleline(pattern).hint_text("Regular Expression"));
+        },
+        Assertion::JsonPathEquals { path, value } => {
+            ui.add(TextEdit::singleline(path).hint_text("$.data.id").desired_width(128.));
+            ui.label("=");
+            ui.add(TextEdit::singleline(value).hint_text("Value"));
+        },
+        Assertion::JsonPathExists { path } => {
+            ui.add(TextEdit::singleline(path).hint_text("$.data.id"));
+        },
+        Assertion::BodyContains { text } => {
+            ui.add(TextEdit::singleline(text).hint_text("Text"));
+        },
+        Assertion::ResponseTimeBelow { millis } => {
+            ui.add(DragValue::new(millis).suffix(" ms"));
+        },
+    }
+}
+
+impl Tab for TestsTab {
+    type T = RequestData;
+
+    fn render(&mut self, ui: &mut Ui, request_data: &mut Self::T) {
+        let mut remove_assertion = None;
+        for (i, assertion) in request_data.assertions.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source(("assertion_kind", i))
+                    .selected_text(assertion.kind_name())
+                    .show_ui(ui, |ui| {
+                        for kind in Assertion::all() {
+                            let name = kind.kind_name();
+                            let selected = name == assertion.kind_name();
+                            if ui.selectable_label(selected, name).clicked() && !selected {
+                                *assertion = kind;
+                            }
+                        }
+                    });
+                render_assertion(ui, assertion);
+                if ui.button("x").clicked() {
+                    remove_assertion = Some(i);
+                }
+            });
+        }
+        if let Some(i) = remove_assertion {
+            request_data.assertions.remove(i);
+        }
+
+        ui.menu_button("Add Assertion", |ui| {
+            for kind in Assertion::all() {
+                if ui.button(kind.kind_name()).clicked() {
+                    request_data.assertions.push(kind);
+                    ui.close_menu();
+                }
+            }
+        });
+    }
+}
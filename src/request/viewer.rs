@@ -0,0 +1,546 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Shows response bodies depending on their content type
+
+use std::borrow::Cow;
+
+use egui::text::LayoutJob;
+use egui::{Color32, ColorImage, ScrollArea, TextEdit, TextStyle, TextureHandle, Ui};
+use encoding_rs::{Encoding, UTF_8};
+use once_cell::sync::Lazy;
+use regex::bytes::Regex;
+use uuid::Uuid;
+
+use crate::engine::ResponseData;
+use crate::find::FindBar;
+use crate::highlight::{self, Language};
+use crate::jwt;
+
+use super::json_tree::JsonTree;
+
+/// Longer bodies are cut off in the viewer, so it stays responsive
+const PREVIEW_CHARS: usize = 64 * 1024;
+const HEX_COLUMNS: usize = 16;
+
+static PDF_PAGE: Lazy<Regex> = Lazy::new(|| Regex::new(r"/Type\s*/Page\b").unwrap());
+static PDF_FIELD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?s-u)/(Title|Author|Subject|Keywords|Creator|Producer|CreationDate|ModDate)\s*\(((?:[^()\\]|\\.)*)\)").unwrap()
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Renderer {
+    Text,
+    Json,
+    /// The text of a page without its tags and scripts
+    Html,
+    /// Markup as it was received
+    Source,
+    Image,
+    Hex,
+    Pdf,
+}
+
+impl Renderer {
+    pub const ALL: [Renderer; 7] = [
+        Renderer::Text,
+        Renderer::Json,
+        Renderer::Html,
+        Renderer::Source,
+        Renderer::Image,
+        Renderer::Hex,
+        Renderer::Pdf,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Renderer::Text => "Text",
+            Renderer::Json => "JSON Tree",
+            Renderer::Html => "HTML",
+            Renderer::Source => "Source",
+            Renderer::Image => "Image",
+            Renderer::Hex => "Hex",
+            Renderer::Pdf => "PDF",
+        }
+    }
+
+    /// Picks a renderer from the `Content-Type`. Looks at the body if the type doesn't say what it is
+    pub fn detect(content_type: Option<&str>, body: &[u8]) -> Self {
+        let mime = content_type
+            .and_then(|t| t.split(';').next())
+            .map(|t| t.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+        match mime.as_str() {
+            "image/png" | "image/jpeg" | "image/jpg" | "image/gif" | "image/webp" => Renderer::Image,
+            "text/html" | "application/xhtml+xml" => Renderer::Html,
+            "application/pdf" => Renderer::Pdf,
+            "application/json" => Renderer::Json,
+            _ if mime.ends_with("+json") => Renderer::Json,
+            "" | "application/octet-stream" => match sniff(body) {
+                Some(renderer) => renderer,
+                None if mime.is_empty() && !is_binary(body) => Renderer::Text,
+                None => Renderer::Hex,
+            },
+            _ if mime.starts_with("text/") || !is_binary(body) => Renderer::Text,
+            _ => Renderer::Hex,
+        }
+    }
+}
+
+/// Recognizes images and PDFs by their first bytes
+fn sniff(body: &[u8]) -> Option<Renderer> {
+    let image = body.starts_with(b"\x89PNG\r\n\x1a\n")
+        || body.starts_with(&[0xff, 0xd8, 0xff])
+        || body.starts_with(b"GIF87a")
+        || body.starts_with(b"GIF89a")
+        || body.len() >= 12 && body.starts_with(b"RIFF") && &body[8..12] == b"WEBP";
+    match image {
+        true => Some(Renderer::Image),
+        false if body.starts_with(b"%PDF-") => Some(Renderer::Pdf),
+        false => None,
+    }
+}
+
+fn is_binary(body: &[u8]) -> bool {
+    body.contains(&0)
+}
+
+/// Decodes the body with the charset from the `Content-Type`, or a byte order mark, or as UTF-8
+pub fn decode<'a>(content_type: Option<&str>, body: &'a [u8]) -> Cow<'a, str> {
+    let encoding = content_type
+        .and_then(|t| t.split(';').skip(1).find_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            key.trim().eq_ignore_ascii_case("charset").then(|| value.trim().trim_matches('"'))
+        }))
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .unwrap_or(UTF_8);
+    encoding.decode(body).0
+}
+
+/// The text of an HTML page. Scripts, styles and comments are left out and tags are dropped
+pub fn html_to_text(html: &str) -> String {
+    const HIDDEN: [&str; 5] = ["script", "style", "head", "noscript", "template"];
+    const BLOCKS: [&str; 16] = ["p", "div", "br", "li", "tr", "h1", "h2", "h3", "h4", "h5", "h6", "section", "article", "header", "footer", "title"];
+
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(end) = rest.find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[1..end];
+        let name = tag.trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        rest = &rest[end + 1..];
+        let opens = !tag.starts_with('/') && !tag.ends_with('/');
+        if opens && HIDDEN.contains(&name.as_str()) {
+            // Skip everything up to the closing tag
+            let closing = format!("</{}", name);
+            rest = find_ignoring_case(rest, &closing)
+                .and_then(|i| rest[i..].find('>').map(|end| &rest[i + end + 1..]))
+                .unwrap_or("");
+        } else if BLOCKS.contains(&name.as_str()) {
+            text.push('\n');
+        }
+    }
+    text.push_str(rest);
+
+    let text = decode_entities(&text);
+    // Collapse whitespace the way a browser does, but keep one empty line between blocks
+    let mut lines: Vec<String> = vec![];
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !line.is_empty() || lines.last().map_or(false, |last| !last.is_empty()) {
+            lines.push(line);
+        }
+    }
+    while lines.last().map_or(false, |last| last.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+fn find_ignoring_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack.to_ascii_lowercase().find(&needle.to_ascii_lowercase())
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';').filter(|&end| end <= 10).map(|end| &rest[1..end + 1]);
+        let c = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => match entity.strip_prefix('#') {
+                Some(number) => match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => number.parse().ok(),
+                }.and_then(char::from_u32),
+                None => None,
+            },
+        });
+        match (entity, c) {
+            (Some(entity), Some(c)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            },
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// One line of a hex dump, like `00000010  48 65 6c 6c 6f  |Hello|`
+pub fn hex_line(offset: usize, bytes: &[u8]) -> String {
+    let mut line = format!("{:08x} ", offset);
+    for i in 0..HEX_COLUMNS {
+        if i % 8 == 0 {
+            line.push(' ');
+        }
+        match bytes.get(i) {
+            Some(byte) => line.push_str(&format!("{:02x} ", byte)),
+            None => line.push_str("   "),
+        }
+    }
+    line.push('|');
+    line.extend(bytes.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }));
+    line.push('|');
+    line
+}
+
+/// What a PDF says about itself
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PdfInfo {
+    pub version: String,
+    pub pages: usize,
+    /// Title, author and the like from the document information
+    pub fields: Vec<(String, String)>,
+}
+
+/// Reads the version, the number of pages and the document information of a PDF without parsing all of it
+pub fn pdf_info(body: &[u8]) -> Option<PdfInfo> {
+    let header = body.strip_prefix(b"%PDF-")?;
+    let version = header.iter().take_while(|b| b.is_ascii_digit() || **b == b'.').map(|&b| b as char).collect();
+    let pages = PDF_PAGE.find_iter(body).count();
+    let fields = PDF_FIELD
+        .captures_iter(body)
+        .map(|c| (String::from_utf8_lossy(&c[1]).into_owned(), pdf_string(&c[2])))
+        .collect();
+    Some(PdfInfo { version, pages, fields })
+}
+
+/// A literal string of a PDF, without its escapes. Strings with a byte order mark are UTF-16
+fn pdf_string(raw: &[u8]) -> String {
+    let mut bytes = vec![];
+    let mut iter = raw.iter();
+    while let Some(&b) = iter.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        match iter.next() {
+            Some(b'n') => bytes.push(b'\n'),
+            Some(b'r') => bytes.push(b'\r'),
+            Some(b't') => bytes.push(b'\t'),
+            Some(&other) => bytes.push(other),
+            None => {},
+        }
+    }
+    match bytes.strip_prefix(&[0xfe, 0xff]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units)
+        },
+        None => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// Remembers how the body of a response is shown
+#[derive(Default)]
+pub struct Viewer {
+    /// Chosen by the user instead of the one from the content type
+    pub renderer: Option<Renderer>,
+    /// The decoded image and the run it is from, so it is decoded only once
+    image: Option<(usize, Result<TextureHandle, String>)>,
+    /// The JWTs in the body, for the run and the renderer they were looked for with
+    tokens: Option<(usize, Renderer, Vec<String>)>,
+    /// The decoded body, the text of a page and what a PDF says about itself, each with the run it is from
+    text: Option<(usize, String)>,
+    html: Option<(usize, String)>,
+    pdf: Option<(usize, Option<PdfInfo>)>,
+    json: JsonTree,
+    pub find: FindBar,
+}
+
+impl Viewer {
+    /// The renderer the body is shown with
+    pub fn renderer(&self, response: &ResponseData) -> Renderer {
+        self.renderer.unwrap_or_else(|| Renderer::detect(response.header("Content-Type"), &response.body))
+    }
+
+    /// The JWTs in a body that is shown as text. They are only looked for once per run
+    pub fn tokens(&mut self, response: &ResponseData, run: usize) -> &[String] {
+        let renderer = self.renderer(response);
+        if !matches!(&self.tokens, Some((tokens_run, tokens_renderer, _)) if *tokens_run == run && *tokens_renderer == renderer) {
+            let tokens = match renderer {
+                Renderer::Text | Renderer::Json | Renderer::Html | Renderer::Source => {
+                    jwt::find_tokens(&response.text()).into_iter().map(String::from).collect()
+                },
+                _ => vec![],
+            };
+            self.tokens = Some((run, renderer, tokens));
+        }
+        self.tokens.as_ref().map_or(&[], |(_, _, tokens)| tokens)
+    }
+
+    pub fn render_picker(&mut self, ui: &mut Ui, response: &ResponseData) {
+        let detected = Renderer::detect(response.header("Content-Type"), &response.body);
+        egui::ComboBox::from_id_source("response_renderer")
+            .selected_text(self.renderer(response).name())
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.renderer, None, format!("Automatic ({})", detected.name()));
+                for renderer in Renderer::ALL {
+                    ui.selectable_value(&mut self.renderer, Some(renderer), renderer.name());
+                }
+            });
+    }
+
+    /// Shows the body of a response of the request with this `uuid`. `run` tells responses apart, so a new one is decoded and parsed again
+    pub fn render(&mut self, ui: &mut Ui, uuid: Uuid, response: &ResponseData, run: usize) {
+        match self.renderer(response) {
+            Renderer::Text => self.render_code(ui, response, run),
+            Renderer::Json if response.is_truncated() => {
+                ui.weak("Only the start of the body was kept, so it can't be shown as a tree.");
+                self.render_code(ui, response, run);
+            },
+            // The tree can't show matches, so the body is shown as text while finding
+            Renderer::Json if self.find.open => self.render_code(ui, response, run),
+            Renderer::Json => {
+                if !self.json.render(ui, uuid, &response.body, run) {
+                    ui.weak("The body isn't valid JSON.");
+                    self.render_code(ui, response, run);
+                }
+            },
+            Renderer::Source => self.render_code(ui, response, run),
+            Renderer::Html => {
+                let text = cached(&mut self.text, run, || decode(response.header("Content-Type"), &response.body).into_owned());
+                let html = cached(&mut self.html, run, || html_to_text(text));
+                render_text(ui, &mut self.find, html, (run, Renderer::Html), response, None);
+            },
+            Renderer::Image => self.render_image(ui, response, run),
+            Renderer::Hex => render_hex(ui, response),
+            Renderer::Pdf => render_pdf(ui, cached(&mut self.pdf, run, || pdf_info(&response.body)).as_ref(), response),
+        }
+    }
+
+    /// The body as highlighted code, in the language of its `Content-Type`
+    fn render_code(&mut self, ui: &mut Ui, response: &ResponseData, run: usize) {
+        let content_type = response.header("Content-Type");
+        let text = cached(&mut self.text, run, || decode(content_type, &response.body).into_owned());
+        let language = Language::detect(content_type, text);
+        render_text(ui, &mut self.find, text, (run, Renderer::Text), response, Some(language));
+    }
+
+    fn render_image(&mut self, ui: &mut Ui, response: &ResponseData, run: usize) {
+        if response.is_truncated() {
+            ui.weak("Only the start of the image was kept. Open the downloaded file to see it.");
+            return;
+        }
+        if self.image.as_ref().map_or(true, |(image_run, _)| *image_run != run) {
+            let texture = image::load_from_memory(&response.body)
+                .map(|image| {
+                    let image = image.to_rgba8();
+                    let size = [image.width() as usize, image.height() as usize];
+                    ui.ctx().load_texture("response_image", ColorImage::from_rgba_unmultiplied(size, image.as_raw()), Default::default())
+                })
+                .map_err(|e| e.to_string());
+            self.image = Some((run, texture));
+        }
+        match &self.image {
+            Some((_, Ok(texture))) => {
+                let size = texture.size_vec2();
+                ui.label(format!("{} × {} pixels", size.x, size.y));
+                ScrollArea::both().show(ui, |ui| {
+                    // Large images are scaled down to the width of the panel, small ones are kept as they are
+                    let scale = (ui.available_width() / size.x).min(1.);
+                    ui.image(texture.id(), size * scale);
+                });
+            },
+            Some((_, Err(e))) => {
+                ui.colored_label(Color32::RED, format!("The image couldn't be decoded: {}", e));
+            },
+            None => {},
+        }
+    }
+}
+
+/// The value in the cache, computed again if it is from another run
+fn cached<T>(cache: &mut Option<(usize, T)>, run: usize, compute: impl FnOnce() -> T) -> &T {
+    if cache.as_ref().map_or(true, |(cached_run, _)| *cached_run != run) {
+        *cache = Some((run, compute()));
+    }
+    &cache.as_ref().unwrap().1
+}
+
+/// Shows the text, highlighted if it has a language. `source` is the run and the renderer the text is from
+fn render_text(ui: &mut Ui, find: &mut FindBar, text: &str, source: (usize, Renderer), response: &ResponseData, language: Option<Language>) {
+    let mut end = text.len().min(PREVIEW_CHARS);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    if end < text.len() || response.is_truncated() {
+        // A truncated body can't be saved from here, it is already in the downloaded file
+        let hint = match response.is_truncated() {
+            true => "Open the downloaded file",
+            false => "Save the response",
+        };
+        ui.colored_label(Color32::YELLOW, format!(
+            "Showing the first {} of {}. {} to see all of it.",
+            super::format_size(end as u64),
+            super::format_size(response.size),
+            hint,
+        ));
+    }
+    // The whole text is searched, so matches after the preview are counted too
+    find.render_preview(ui, text, end, source);
+    let mut response_text = &text[..end];
+    let output = {
+        let find = &*find;
+        let mut layouter = |ui: &Ui, text: &str, wrap_width: f32| {
+            let job = match language {
+                Some(language) => highlight::job(ui, text, language, false),
+                None => LayoutJob::simple(text.to_owned(), TextStyle::Body.resolve(ui.style()), ui.visuals().text_color(), wrap_width),
+            };
+            find.layout(ui, job, wrap_width)
+        };
+        let mut textedit = TextEdit::multiline(&mut response_text).frame(true).layouter(&mut layouter);
+        if language.is_some() {
+            textedit = textedit.code_editor();
+        }
+        ScrollArea::horizontal().show(ui, |ui| textedit.min_size(ui.available_size()).show(ui)).inner
+    };
+    find.scroll_to_match(ui, &output);
+}
+
+fn render_hex(ui: &mut Ui, response: &ResponseData) {
+    if response.is_truncated() {
+        ui.colored_label(Color32::YELLOW, format!(
+            "Showing the first {} of {}.",
+            super::format_size(response.body.len() as u64),
+            super::format_size(response.size),
+        ));
+    }
+    let rows = (response.body.len() + HEX_COLUMNS - 1) / HEX_COLUMNS;
+    let row_height = ui.text_style_height(&TextStyle::Monospace);
+    ScrollArea::both().auto_shrink([false, false]).show_rows(ui, row_height, rows, |ui, range| {
+        for row in range {
+            let start = row * HEX_COLUMNS;
+            let end = (start + HEX_COLUMNS).min(response.body.len());
+            ui.monospace(hex_line(start, &response.body[start..end]));
+        }
+    });
+}
+
+fn render_pdf(ui: &mut Ui, info: Option<&PdfInfo>, response: &ResponseData) {
+    let Some(info) = info else {
+        ui.colored_label(Color32::RED, "This is not a PDF document.");
+        return;
+    };
+    egui::Grid::new("pdf_info").num_columns(2).striped(true).show(ui, |ui| {
+        ui.strong("Version");
+        ui.label(&info.version);
+        ui.end_row();
+        ui.strong("Pages");
+        ui.label(info.pages.to_string());
+        ui.end_row();
+        for (key, value) in &info.fields {
+            ui.strong(key);
+            ui.label(value);
+            ui.end_row();
+        }
+    });
+    if response.is_truncated() {
+        ui.weak("Only the start of the document was read, so some of this may be missing.");
+        ui.weak("Open the downloaded file to see the document.");
+    } else {
+        ui.weak("Save the response to open the document.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_renderers() {
+        assert_eq!(Renderer::detect(Some("image/png"), b""), Renderer::Image);
+        assert_eq!(Renderer::detect(Some("text/html; charset=utf-8"), b"<p>"), Renderer::Html);
+        assert_eq!(Renderer::detect(Some("application/octet-stream"), b"\x01\x02"), Renderer::Hex);
+        assert_eq!(Renderer::detect(Some("application/octet-stream"), b"\x89PNG\r\n\x1a\n...."), Renderer::Image);
+        assert_eq!(Renderer::detect(None, b"%PDF-1.7"), Renderer::Pdf);
+        assert_eq!(Renderer::detect(Some("application/json"), b"{}"), Renderer::Json);
+        assert_eq!(Renderer::detect(Some("application/problem+json"), b"{}"), Renderer::Json);
+        assert_eq!(Renderer::detect(Some("text/plain"), b"{}"), Renderer::Text);
+        assert_eq!(Renderer::detect(Some("application/zip"), b"PK\x03\x04\x00"), Renderer::Hex);
+    }
+
+    #[test]
+    fn decodes_the_charset() {
+        assert_eq!(decode(Some("text/plain; charset=ISO-8859-1"), b"caf\xe9"), "café");
+        assert_eq!(decode(Some("text/plain; charset=\"windows-1252\""), b"\x80"), "€");
+        assert_eq!(decode(None, "café".as_bytes()), "café");
+    }
+
+    #[test]
+    fn html_becomes_text() {
+        let html = "<html><head><title>Hidden</title><style>p { color: red }</style></head>\
+            <body><h1>Title &amp; more</h1><!-- a comment --><script>alert('<p>')</script>\
+            <p>First   paragraph</p><p>Second&nbsp;one &#x2764;</p></body></html>";
+        assert_eq!(html_to_text(html), "Title & more\n\nFirst paragraph\n\nSecond one ❤");
+    }
+
+    #[test]
+    fn hex_lines_line_up() {
+        let line = hex_line(16, b"Hello\x00");
+        assert_eq!(line, format!("00000010  48 65 6c 6c 6f 00 {}|Hello.|", " ".repeat(3 * 10 + 1)));
+        assert_eq!(hex_line(0, &[0x41; 16]).len(), line.len() - 6 + 16);
+    }
+
+    #[test]
+    fn reads_pdf_metadata() {
+        let pdf = b"%PDF-1.4\n1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj\n\
+            2 0 obj << /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 >> endobj\n\
+            3 0 obj << /Type /Page >> endobj 4 0 obj << /Type/Page >> endobj\n\
+            5 0 obj << /Title (Quarterly \\(draft\\)) /Author (\xfe\xff\x00A\x00n\x00n) >> endobj";
+        let info = pdf_info(pdf).unwrap();
+        assert_eq!(info.version, "1.4");
+        assert_eq!(info.pages, 2);
+        assert_eq!(info.fields, vec![
+            (String::from("Title"), String::from("Quarterly (draft)")),
+            (String::from("Author"), String::from("Ann")),
+        ]);
+        assert!(pdf_info(b"<html>").is_none());
+    }
+}
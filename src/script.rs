@@ -0,0 +1,233 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Runs pre-request scripts with [rhai](https://rhai.rs).
+//!
+//! A script sees the request as the variables `url`, `method`, `headers`, `body` and `variables`
+//! and can change all of them. `print` and `log` write to the console of the request.
+//! `headers` only holds the last value of a repeated header, but headers the script doesn't change
+//! are sent as they were.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use rhai::{Dynamic, Engine, Map, Scope};
+use ring::{digest, hmac};
+use uuid::Uuid;
+
+use crate::request::RequestData;
+use crate::request::tabs::body_tab::{BodyData, BodyType};
+
+/// Keeps a script with an endless loop from freezing the app
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+pub struct ScriptContext {
+    pub request_data: RequestData,
+    pub variables: BTreeMap<String, String>,
+    pub console: Vec<String>,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn timestamp() -> std::time::Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+fn create_engine(console: Rc<RefCell<Vec<String>>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let print_console = Rc::clone(&console);
+    engine.on_print(move |s| print_console.borrow_mut().push(s.to_string()));
+    let debug_console = Rc::clone(&console);
+    engine.on_debug(move |s, _, _| debug_console.borrow_mut().push(s.to_string()));
+    engine.register_fn("log", move |s: &str| console.borrow_mut().push(s.to_string()));
+
+    engine.register_fn("timestamp", || timestamp().as_secs() as i64);
+    engine.register_fn("timestamp_ms", || timestamp().as_millis() as i64);
+    engine.register_fn("uuid", || Uuid::new_v4().to_string());
+    engine.register_fn("base64", |s: &str| STANDARD.encode(s));
+    engine.register_fn("sha256", |s: &str| to_hex(digest::digest(&digest::SHA256, s.as_bytes()).as_ref()));
+    engine.register_fn("hmac_sha256", |key: &str, message: &str| {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, key.as_bytes());
+        to_hex(hmac::sign(&key, message.as_bytes()).as_ref())
+    });
+    engine
+}
+
+fn to_map(pairs: impl IntoIterator<Item = (String, String)>) -> Map {
+    pairs.into_iter().map(|(k, v)| (k.into(), Dynamic::from(v))).collect()
+}
+
+fn from_map(map: Map) -> BTreeMap<String, String> {
+    map.into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+/// Writes what the script changed in the map back into the pairs. Keys the script didn't touch keep
+/// all of their pairs, so repeated headers survive. New keys are appended
+pub fn update_pairs(pairs: &mut Vec<(String, String)>, before: &BTreeMap<String, String>, mut after: BTreeMap<String, String>) {
+    let mut written = BTreeSet::new();
+    pairs.retain_mut(|(key, value)| {
+        if before.get(key) == after.get(key) {
+            return true;
+        }
+        match after.get(key) {
+            // A changed key that was repeated is only kept once
+            Some(new_value) if written.insert(key.clone()) => {
+                *value = new_value.clone();
+                true
+            },
+            _ => false,
+        }
+    });
+    after.retain(|key, _| !pairs.iter().any(|(existing, _)| existing == key));
+    pairs.extend(after);
+}
+
+/// Runs the script, writing everything it changed back into the context
+pub fn run(script: &str, context: &mut ScriptContext) -> anyhow::Result<()> {
+    if script.trim().is_empty() {
+        return Ok(());
+    }
+
+    let console = Rc::new(RefCell::new(vec![]));
+    let engine = create_engine(Rc::clone(&console));
+
+    let request_data = &mut context.request_data;
+    let body = match request_data.body.get(&request_data.selected_body) {
+        Some(BodyData::Raw { data }) => data.clone(),
+        _ => String::new(),
+    };
+
+    let headers = to_map(request_data.headers.clone());
+    let headers_before = from_map(headers.clone());
+
+    let mut scope = Scope::new();
+    scope.push("url", request_data.url_string.clone());
+    scope.push("method", request_data.method.to_string());
+    scope.push("headers", headers);
+    scope.push("body", body.clone());
+    scope.push("variables", to_map(context.variables.clone()));
+
+    let result = engine.run_with_scope(&mut scope, script);
+    context.console.append(&mut console.borrow_mut());
+    result.map_err(|e| anyhow!("Script error: {}", e))?;
+
+    let get_string = |name: &str| -> anyhow::Result<String> {
+        scope.get_value::<Dynamic>(name)
+            .map(|v| v.to_string())
+            .ok_or_else(|| anyhow!("The script removed `{}`", name))
+    };
+    let get_map = |name: &str| -> anyhow::Result<BTreeMap<String, String>> {
+        scope.get_value::<Map>(name)
+            .map(from_map)
+            .ok_or_else(|| anyhow!("`{}` has to stay a map", name))
+    };
+
+    request_data.url_string = get_string("url")?;
+    request_data.method = get_string("method")?.parse()?;
+
+    update_pairs(&mut request_data.headers, &headers_before, get_map("headers")?);
+
+    let new_body = get_string("body")?;
+    if new_body != body {
+        request_data.selected_body = BodyType::Raw;
+        request_data.body.insert(BodyType::Raw, BodyData::Raw { data: new_body });
+    }
+
+    context.variables = get_map("variables")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::request::RequestMethod;
+
+    use super::*;
+
+    fn pair(key: &str, value: &str) -> (String, String) {
+        (key.to_owned(), value.to_owned())
+    }
+
+    fn context() -> ScriptContext {
+        let mut request_data = RequestData {
+            url_string: String::from("https://example.com/users"),
+            headers: vec![pair("Accept", "text/html"), pair("Accept", "application/json"), pair("X-Trace", "1")],
+            ..Default::default()
+        };
+        request_data.body.insert(BodyType::Raw, BodyData::Raw { data: String::from("{}") });
+        request_data.selected_body = BodyType::Raw;
+        ScriptContext {
+            request_data,
+            variables: BTreeMap::from([(String::from("host"), String::from("localhost"))]),
+            console: vec![],
+        }
+    }
+
+    #[test]
+    fn changes_are_written_back() {
+        let mut context = context();
+        run(r#"
+            url += "?page=2";
+            method = "POST";
+            body = `{"name":"Ada"}`;
+            variables.token = "abc";
+            print("done");
+        "#, &mut context).unwrap();
+
+        let request_data = &context.request_data;
+        assert_eq!(request_data.url_string, "https://example.com/users?page=2");
+        assert_eq!(request_data.method, RequestMethod::Post);
+        assert_eq!(request_data.body[&BodyType::Raw], BodyData::Raw { data: String::from(r#"{"name":"Ada"}"#) });
+        assert_eq!(context.variables["host"], "localhost");
+        assert_eq!(context.variables["token"], "abc");
+        assert_eq!(context.console, vec![String::from("done")]);
+    }
+
+    #[test]
+    fn only_changed_headers_are_written_back() {
+        let mut context = context();
+        run(r#"headers["X-Signature"] = sha256("abc");"#, &mut context).unwrap();
+        // The repeated header is kept as it was
+        assert_eq!(context.request_data.headers, vec![
+            pair("Accept", "text/html"),
+            pair("Accept", "application/json"),
+            pair("X-Trace", "1"),
+            pair("X-Signature", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+        ]);
+
+        run(r#"headers.Accept = "text/plain"; headers.remove("X-Trace");"#, &mut context).unwrap();
+        assert_eq!(context.request_data.headers, vec![
+            pair("Accept", "text/plain"),
+            pair("X-Signature", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+        ]);
+    }
+
+    #[test]
+    fn errors_are_returned_with_the_console() {
+        let mut context = context();
+        let error = run(r#"print("before"); throw "no token";"#, &mut context).unwrap_err();
+        assert!(error.to_string().starts_with("Script error"));
+        assert_eq!(context.console, vec![String::from("before")]);
+        // Nothing is written back
+        assert_eq!(context.request_data.url_string, "https://example.com/users");
+
+        let error = run(r#"headers = "none";"#, &mut context).unwrap_err();
+        assert_eq!(error.to_string(), "`headers` has to stay a map");
+        assert!(run(r#"method = "FETCH";"#, &mut context).is_err());
+    }
+
+    #[test]
+    fn endless_loops_are_stopped() {
+        let mut context = context();
+        let error = run("loop { }", &mut context).unwrap_err();
+        assert!(error.to_string().contains("Too many operations"), "{}", error);
+    }
+}
@@ -0,0 +1,273 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Finds collections, folders and requests by name, method, URL, headers or body
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+use uuid::Uuid;
+
+use crate::collection::{Collection, Items};
+use crate::request::{RequestData, RequestMethod};
+use crate::request::tabs::body_tab::BodyData;
+
+/// Text that contains the query, with the range of the match in it
+#[derive(Debug, PartialEq, Eq)]
+pub struct Match {
+    /// Where the text is from, like "URL" or "Body"
+    pub field: &'static str,
+    pub text: String,
+    pub range: Range<usize>,
+}
+
+#[derive(Debug)]
+pub struct SearchResult {
+    pub uuid: Uuid,
+    /// The names of the collection and folders the result is in
+    pub path: String,
+    pub name: String,
+    /// `None` for collections and folders
+    pub method: Option<RequestMethod>,
+    /// Where the query is in the name, if it is there
+    pub name_range: Option<Range<usize>>,
+    /// The first other thing that matched if the name didn't
+    pub detail: Option<Match>,
+}
+
+/// Everything that matches the query in the order it is shown in the sidebar. Ignores case
+pub fn search(collections: &[Collection], query: &str) -> Vec<SearchResult> {
+    let query = query.trim();
+    let mut results = vec![];
+    if query.is_empty() {
+        return results;
+    }
+    for collection in collections {
+        if let Some(range) = find(&collection.name, query) {
+            results.push(SearchResult {
+                uuid: collection.uuid,
+                path: String::new(),
+                name: collection.name.clone(),
+                method: None,
+                name_range: Some(range),
+                detail: None,
+            });
+        }
+        search_items(&collection.items, &collection.name, query, &mut results);
+    }
+    results
+}
+
+/// Changes when anything the search looks at changes, so the results only have to be found again then
+pub fn fingerprint(collections: &[Collection]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for collection in collections {
+        (&collection.uuid, &collection.name).hash(&mut hasher);
+        hash_items(&collection.items, &mut hasher);
+    }
+    hasher.finish()
+}
+
+fn hash_items(items: &Items, hasher: &mut DefaultHasher) {
+    for folder in &items.folders {
+        (&folder.uuid, &folder.name).hash(hasher);
+        hash_items(&folder.items, hasher);
+    }
+    for request in &items.requests {
+        let data = request.request_data();
+        (&request.uuid, &data.name, &data.method, &data.url_string, &data.headers).hash(hasher);
+        data.body.get(&data.selected_body).hash(hasher);
+    }
+}
+
+fn search_items(items: &Items, path: &str, query: &str, results: &mut Vec<SearchResult>) {
+    for folder in &items.folders {
+        if let Some(range) = find(&folder.name, query) {
+            results.push(SearchResult {
+                uuid: folder.uuid,
+                path: path.to_owned(),
+                name: folder.name.clone(),
+                method: None,
+                name_range: Some(range),
+                detail: None,
+            });
+        }
+        search_items(&folder.items, &format!("{} / {}", path, folder.name), query, results);
+    }
+    for request in &items.requests {
+        let data = request.request_data();
+        let name_range = find(&data.name, query);
+        let detail = match name_range {
+            Some(_) => None,
+            None => match_request(data, query),
+        };
+        if name_range.is_some() || detail.is_some() {
+            results.push(SearchResult {
+                uuid: request.uuid,
+                path: path.to_owned(),
+                name: data.name.clone(),
+                method: Some(data.method.clone()),
+                name_range,
+                detail,
+            });
+        }
+    }
+}
+
+/// Looks for the query in everything of the request except its name
+fn match_request(data: &RequestData, query: &str) -> Option<Match> {
+    let method = data.method.to_string();
+    if let Some(range) = find(&method, query) {
+        return Some(Match { field: "Method", text: method, range });
+    }
+    if let Some(range) = find(&data.url_string, query) {
+        return Some(snippet("URL", &data.url_string, range));
+    }
+    for (key, value) in &data.headers {
+        let header = format!("{}: {}", key, value);
+        if let Some(range) = find(&header, query) {
+            return Some(snippet("Header", &header, range));
+        }
+    }
+    if let Some(BodyData::Raw { data }) = data.body.get(&data.selected_body) {
+        if let Some(range) = find(data, query) {
+            return Some(snippet("Body", data, range));
+        }
+    }
+    None
+}
+
+/// The position of `needle` in `haystack`, ignoring case
+fn find(haystack: &str, needle: &str) -> Option<Range<usize>> {
+    if needle.is_empty() {
+        return None;
+    }
+    haystack.char_indices().find_map(|(start, _)| {
+        let mut rest = haystack[start..].char_indices();
+        let mut end = start;
+        for n in needle.chars() {
+            let (i, c) = rest.next()?;
+            if !c.to_lowercase().eq(n.to_lowercase()) {
+                return None;
+            }
+            end = start + i + c.len_utf8();
+        }
+        Some(start..end)
+    })
+}
+
+/// A single line of the text around the match, so long bodies fit into the sidebar
+fn snippet(field: &'static str, text: &str, range: Range<usize>) -> Match {
+    const BEFORE: usize = 20;
+    const AFTER: usize = 40;
+
+    let start = text[..range.start].char_indices().rev().nth(BEFORE - 1).map_or(0, |(i, _)| i);
+    let end = text[range.end..].char_indices().nth(AFTER).map_or(text.len(), |(i, _)| range.end + i);
+    let prefix = if start > 0 { "…" } else { "" };
+    let suffix = if end < text.len() { "…" } else { "" };
+
+    let line: String = text[start..end].chars()
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .collect();
+    // Whitespace is replaced by a space, which can be shorter than the original character
+    let match_start = prefix.len() + line_len(&text[start..range.start]);
+    let match_end = match_start + line_len(&text[range.clone()]);
+    Match {
+        field,
+        text: format!("{}{}{}", prefix, line, suffix),
+        range: match_start..match_end,
+    }
+}
+
+fn line_len(text: &str) -> usize {
+    text.chars().map(|c| if c.is_whitespace() { 1 } else { c.len_utf8() }).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::request::tabs::body_tab::BodyType;
+
+    fn collection() -> Collection {
+        let mut collection = Collection::new(String::from("Shop"));
+        let folder = collection.create_folder(None).unwrap();
+        let uuid = collection.create_request(Some(&folder)).unwrap();
+        let request = collection.items.find_request_mut(&uuid).unwrap();
+        let mut data = request.request_data().clone();
+        data.name = String::from("Create order");
+        data.method = RequestMethod::Post;
+        data.url_string = String::from("https://shop.example/orders");
+        data.headers.push((String::from("X-Tenant"), String::from("ACME")));
+        data.selected_body = BodyType::Raw;
+        data.body.insert(BodyType::Raw, BodyData::Raw { data: String::from("{\n  \"sku\": \"Widget-42\"\n}") });
+        *request = crate::request::Request::with_data(data, Default::default());
+        collection.relink();
+        collection
+    }
+
+    #[test]
+    fn finds_requests_by_anything() {
+        let collections = vec![collection()];
+
+        let by_name = search(&collections, "ORDER");
+        assert_eq!(by_name.len(), 1);
+        assert_eq!(by_name[0].name_range, Some(7..12));
+        assert_eq!(by_name[0].path, "Shop / New Folder");
+
+        let by_header = search(&collections, "acme");
+        assert_eq!(by_header[0].detail.as_ref().unwrap().field, "Header");
+
+        let by_method = search(&collections, "post");
+        assert_eq!(by_method[0].detail.as_ref().unwrap().text, "POST");
+
+        let by_body = search(&collections, "widget");
+        let detail = by_body[0].detail.as_ref().unwrap();
+        assert_eq!(detail.field, "Body");
+        assert_eq!(&detail.text[detail.range.clone()], "Widget");
+        assert!(!detail.text.contains('\n'));
+
+        assert!(search(&collections, "nothing like this").is_empty());
+        assert!(search(&collections, "  ").is_empty());
+    }
+
+    #[test]
+    fn collections_and_folders_match_by_name() {
+        let collections = vec![collection()];
+        let results = search(&collections, "shop");
+        // The collection and the request with the URL
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].uuid, collections[0].uuid);
+        assert_eq!(results[1].detail.as_ref().unwrap().field, "URL");
+
+        let results = search(&collections, "folder");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].method.is_none());
+    }
+
+    #[test]
+    fn the_fingerprint_changes_with_what_is_searched() {
+        let mut collections = vec![collection()];
+        let before = fingerprint(&collections);
+        assert_eq!(fingerprint(&collections), before);
+
+        let folder = collections[0].items.folders[0].uuid;
+        collections[0].items.find_folder_mut(&folder).unwrap().name = String::from("Orders");
+        let renamed = fingerprint(&collections);
+        assert_ne!(renamed, before);
+
+        collections[0].create_request(None);
+        assert_ne!(fingerprint(&collections), renamed);
+    }
+
+    #[test]
+    fn long_text_is_cut_around_the_match() {
+        let text = format!("{}needle{}", "a".repeat(100), "b".repeat(100));
+        let start = text.find("needle").unwrap();
+        let found = snippet("Body", &text, start..start + 6);
+        assert!(found.text.starts_with('…') && found.text.ends_with('…'));
+        assert_eq!(&found.text[found.range.clone()], "needle");
+    }
+}
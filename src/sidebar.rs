@@ -0,0 +1,375 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! The tree of collections, folders and requests in the side panel
+
+use egui::collapsing_header::CollapsingState;
+use egui::text::{LayoutJob, TextFormat};
+use egui::{Ui, Layout, Align, Align2, SelectableLabel, Sense, Rect, Id, CursorIcon, Key, TextEdit, Modifiers, FontId, TextStyle, Color32};
+use uuid::Uuid;
+
+use crate::collection::{Collection, Items};
+use crate::search::{self, SearchResult};
+
+/// Something the user did in the sidebar. They are applied after the sidebar was drawn,
+/// so the collections don't have to be borrowed mutably while drawing them
+pub enum SidebarAction {
+    /// Opens the tab of a collection, folder or request
+    Open(Uuid),
+    NewRequest { collection: Uuid, folder: Option<Uuid> },
+    NewFolder { collection: Uuid, parent: Option<Uuid> },
+    DuplicateRequest(Uuid),
+    DeleteRequest(Uuid),
+    DeleteFolder(Uuid),
+    /// Moves a request into the folder, or the top level of the collection if there is no folder.
+    /// It is put in front of `before`, or at the end
+    MoveRequest { request: Uuid, collection: Uuid, folder: Option<Uuid>, before: Option<Uuid> },
+    /// Renames a collection or folder
+    Rename { uuid: Uuid, name: String },
+    DuplicateCollection(Uuid),
+    /// Only sent after the user confirmed it
+    DeleteCollection(Uuid),
+    CollectionSettings(Uuid),
+}
+
+#[derive(Default)]
+pub struct Sidebar {
+    /// The collection, folder or request that was opened last
+    pub selected: Option<Uuid>,
+    /// The request that is being dragged
+    dragged: Option<Uuid>,
+    /// The collection or folder that is being renamed and its new name
+    renaming: Option<(Uuid, String)>,
+    focus_rename: bool,
+    /// The collection that waits for the user to confirm deleting it
+    confirm_delete: Option<Uuid>,
+    /// Shows matching items instead of the tree if it isn't empty
+    query: String,
+    results: Vec<SearchResult>,
+    /// The query and the [fingerprint](search::fingerprint) of the collections the results are for
+    searched: Option<(String, u64)>,
+    /// The result that enter opens
+    highlighted: usize,
+    scroll_to_highlighted: bool,
+}
+
+impl Sidebar {
+    pub fn focus_search(&self, ctx: &egui::Context) {
+        ctx.memory_mut(|m| m.request_focus(Id::new("sidebar_search")));
+    }
+
+    /// The search box. Arrow keys choose a result, enter opens it and escape clears the search
+    pub fn render_search(&mut self, ui: &mut Ui, collections: &[Collection], actions: &mut Vec<SidebarAction>) {
+        let id = Id::new("sidebar_search");
+        if ui.memory(|m| m.has_focus(id)) && !self.results.is_empty() {
+            let (up, down) = ui.input_mut(|i| (
+                i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                i.consume_key(Modifiers::NONE, Key::ArrowDown),
+            ));
+            if up {
+                self.highlighted = self.highlighted.saturating_sub(1);
+            }
+            if down {
+                self.highlighted = (self.highlighted + 1).min(self.results.len() - 1);
+            }
+            self.scroll_to_highlighted |= up || down;
+        }
+
+        let search = TextEdit::singleline(&mut self.query)
+            .id(id)
+            .hint_text("Search")
+            .desired_width(ui.available_width());
+        let response = ui.add(search);
+        if response.changed() {
+            self.highlighted = 0;
+        }
+        if response.lost_focus() {
+            if ui.input(|i| i.key_pressed(Key::Escape)) {
+                self.query.clear();
+            } else if ui.input(|i| i.key_pressed(Key::Enter)) {
+                if let Some(result) = self.results.get(self.highlighted) {
+                    actions.push(SidebarAction::Open(result.uuid));
+                }
+                response.request_focus();
+            }
+        }
+
+        if self.query.trim().is_empty() {
+            self.results.clear();
+            self.searched = None;
+        } else {
+            let searched = Some((self.query.clone(), search::fingerprint(collections)));
+            if self.searched != searched {
+                self.results = search::search(collections, &self.query);
+                self.searched = searched;
+            }
+        }
+        self.highlighted = self.highlighted.min(self.results.len().saturating_sub(1));
+    }
+
+    pub fn render(&mut self, ui: &mut Ui, collections: &[Collection], actions: &mut Vec<SidebarAction>) {
+        if self.query.trim().is_empty() {
+            for collection in collections {
+                self.render_collection(ui, collection, actions);
+                ui.separator();
+            }
+        } else {
+            self.render_results(ui, actions);
+        }
+        self.render_confirm_delete(ui, collections, actions);
+
+        let Some(dragged) = self.dragged else {
+            return;
+        };
+        if let Some(request) = collections.iter().find_map(|c| c.items.find_request(&dragged)) {
+            egui::show_tooltip_at_pointer(ui.ctx(), Id::new("sidebar_dragged_request"), |ui| {
+                ui.label(request.name());
+            });
+        }
+        ui.ctx().set_cursor_icon(CursorIcon::Grabbing);
+        if ui.input(|i| i.pointer.any_released()) {
+            self.dragged = None;
+        }
+    }
+
+    fn render_results(&mut self, ui: &mut Ui, actions: &mut Vec<SidebarAction>) {
+        if self.results.is_empty() {
+            ui.weak("Nothing found");
+            return;
+        }
+        let scroll = std::mem::take(&mut self.scroll_to_highlighted);
+        for (i, result) in self.results.iter().enumerate() {
+            let response = ui.add(SelectableLabel::new(i == self.highlighted, result_text(ui, result)));
+            if response.clicked() {
+                self.highlighted = i;
+                actions.push(SidebarAction::Open(result.uuid));
+            }
+            if scroll && i == self.highlighted {
+                response.scroll_to_me(None);
+            }
+        }
+    }
+
+    fn render_confirm_delete(&mut self, ui: &mut Ui, collections: &[Collection], actions: &mut Vec<SidebarAction>) {
+        let Some(uuid) = self.confirm_delete else {
+            return;
+        };
+        let Some(collection) = collections.iter().find(|c| c.uuid == uuid) else {
+            self.confirm_delete = None;
+            return;
+        };
+        egui::Window::new("Delete Collection")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, [0., 0.])
+            .show(ui.ctx(), |ui| {
+                ui.label(format!(
+                    "Delete \"{}\" and its {} requests? This cannot be undone.",
+                    collection.name,
+                    collection.items.request_count(),
+                ));
+                ui.add_space(5.);
+                ui.horizontal(|ui| {
+                    if ui.button("Delete").clicked() {
+                        actions.push(SidebarAction::DeleteCollection(uuid));
+                        self.confirm_delete = None;
+                    }
+                    if ui.button("Cancel").clicked() || ui.input(|i| i.key_pressed(Key::Escape)) {
+                        self.confirm_delete = None;
+                    }
+                });
+            });
+    }
+
+    fn start_rename(&mut self, uuid: Uuid, name: &str) {
+        self.renaming = Some((uuid, name.to_owned()));
+        self.focus_rename = true;
+    }
+
+    /// Shows a text field instead of the label if `uuid` is being renamed.
+    /// Enter or clicking somewhere else keeps the name, escape cancels
+    fn render_rename(&mut self, ui: &mut Ui, uuid: Uuid, actions: &mut Vec<SidebarAction>) -> bool {
+        let Some((renaming, name)) = &mut self.renaming else {
+            return false;
+        };
+        if *renaming != uuid {
+            return false;
+        }
+        let response = ui.add(TextEdit::singleline(name).desired_width(ui.available_width()));
+        if std::mem::take(&mut self.focus_rename) {
+            response.request_focus();
+        } else if response.lost_focus() {
+            if !ui.input(|i| i.key_pressed(Key::Escape)) && !name.trim().is_empty() {
+                actions.push(SidebarAction::Rename { uuid, name: name.trim().to_owned() });
+            }
+            self.renaming = None;
+        }
+        true
+    }
+
+    fn render_collection(&mut self, ui: &mut Ui, collection: &Collection, actions: &mut Vec<SidebarAction>) {
+        let id = ui.make_persistent_id(format!("collection_{}", collection.uuid));
+        CollapsingState::load_with_default_open(ui.ctx(), id, true)
+            .show_header(ui, |ui| {
+                let label = SelectableLabel::new(self.selected == Some(collection.uuid), &collection.name);
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    render_add_menu(ui, collection.uuid, None, actions);
+                    if self.render_rename(ui, collection.uuid, actions) {
+                        return;
+                    }
+                    let response = ui.add_sized(ui.available_size(), label);
+                    if response.clicked() {
+                        actions.push(SidebarAction::Open(collection.uuid));
+                    }
+                    if response.double_clicked() {
+                        self.start_rename(collection.uuid, &collection.name);
+                    }
+                    if let Some(request) = self.drop_target(ui, response.rect, false) {
+                        actions.push(SidebarAction::MoveRequest { request, collection: collection.uuid, folder: None, before: None });
+                    }
+                    response.context_menu(|ui| {
+                        if ui.button("Rename").clicked() {
+                            self.start_rename(collection.uuid, &collection.name);
+                            ui.close_menu();
+                        }
+                        if ui.button("Duplicate Collection").clicked() {
+                            actions.push(SidebarAction::DuplicateCollection(collection.uuid));
+                            ui.close_menu();
+                        }
+                        if ui.button("Settings").clicked() {
+                            actions.push(SidebarAction::CollectionSettings(collection.uuid));
+                            ui.close_menu();
+                        }
+                        ui.separator();
+                        if ui.button("Delete Collection…").clicked() {
+                            self.confirm_delete = Some(collection.uuid);
+                            ui.close_menu();
+                        }
+                    });
+                });
+            })
+            .body(|ui| self.render_items(ui, collection.uuid, None, &collection.items, actions));
+    }
+
+    fn render_items(&mut self, ui: &mut Ui, collection: Uuid, parent: Option<Uuid>, items: &Items, actions: &mut Vec<SidebarAction>) {
+        for folder in &items.folders {
+            let id = ui.make_persistent_id(format!("folder_{}", folder.uuid));
+            CollapsingState::load_with_default_open(ui.ctx(), id, false)
+                .show_header(ui, |ui| {
+                    let label = SelectableLabel::new(self.selected == Some(folder.uuid), &folder.name);
+                    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                        render_add_menu(ui, collection, Some(folder.uuid), actions);
+                        if self.render_rename(ui, folder.uuid, actions) {
+                            return;
+                        }
+                        let response = ui.add_sized(ui.available_size(), label);
+                        if response.clicked() {
+                            actions.push(SidebarAction::Open(folder.uuid));
+                        }
+                        if response.double_clicked() {
+                            self.start_rename(folder.uuid, &folder.name);
+                        }
+                        if let Some(request) = self.drop_target(ui, response.rect, false) {
+                            actions.push(SidebarAction::MoveRequest { request, collection, folder: Some(folder.uuid), before: None });
+                        }
+                        response.context_menu(|ui| {
+                            if ui.button("Rename").clicked() {
+                                self.start_rename(folder.uuid, &folder.name);
+                                ui.close_menu();
+                            }
+                            if ui.button("Delete Folder").clicked() {
+                                actions.push(SidebarAction::DeleteFolder(folder.uuid));
+                                ui.close_menu();
+                            }
+                        });
+                    });
+                })
+                .body(|ui| self.render_items(ui, collection, Some(folder.uuid), &folder.items, actions));
+        }
+
+        for request in &items.requests {
+            let label = SelectableLabel::new(self.selected == Some(request.uuid), request.name());
+            let response = ui.add(label).interact(Sense::drag());
+            if response.clicked() {
+                actions.push(SidebarAction::Open(request.uuid));
+            }
+            if response.drag_started() {
+                self.dragged = Some(request.uuid);
+            }
+            if let Some(dragged) = self.drop_target(ui, response.rect, true) {
+                actions.push(SidebarAction::MoveRequest { request: dragged, collection, folder: parent, before: Some(request.uuid) });
+            }
+            response.context_menu(|ui| {
+                if ui.button("Duplicate Request").clicked() {
+                    actions.push(SidebarAction::DuplicateRequest(request.uuid));
+                    ui.close_menu();
+                }
+                if ui.button("Delete").clicked() {
+                    actions.push(SidebarAction::DeleteRequest(request.uuid));
+                    ui.close_menu();
+                }
+            });
+        }
+    }
+
+    /// Shows where the dragged request would go if it is over `rect`, either as a line above the
+    /// rect or as a frame around it. Returns the request when it is dropped
+    fn drop_target(&self, ui: &Ui, rect: Rect, line: bool) -> Option<Uuid> {
+        let dragged = self.dragged?;
+        if !ui.rect_contains_pointer(rect) {
+            return None;
+        }
+        let stroke = ui.visuals().selection.stroke;
+        match line {
+            true => ui.painter().hline(rect.x_range(), rect.top(), stroke),
+            false => ui.painter().rect_stroke(rect, 2., stroke),
+        }
+        ui.input(|i| i.pointer.any_released()).then_some(dragged)
+    }
+}
+
+fn render_add_menu(ui: &mut Ui, collection: Uuid, folder: Option<Uuid>, actions: &mut Vec<SidebarAction>) {
+    ui.menu_button("+", |ui| {
+        if ui.button("New Request").clicked() {
+            actions.push(SidebarAction::NewRequest { collection, folder });
+            ui.close_menu();
+        }
+        if ui.button("New Folder").clicked() {
+            actions.push(SidebarAction::NewFolder { collection, parent: folder });
+            ui.close_menu();
+        }
+    });
+}
+
+/// The method and name of a result with the path and whatever matched below it
+fn result_text(ui: &Ui, result: &SearchResult) -> LayoutJob {
+    let body = TextFormat::simple(TextStyle::Body.resolve(ui.style()), ui.visuals().text_color());
+    let small = TextFormat::simple(TextStyle::Small.resolve(ui.style()), ui.visuals().weak_text_color());
+    let method = TextFormat::simple(FontId::monospace(10.), ui.visuals().weak_text_color());
+    let highlight = ui.visuals().selection.bg_fill;
+
+    let mut job = LayoutJob::default();
+    if let Some(request_method) = &result.method {
+        job.append(&format!("{} ", request_method), 0., method);
+    }
+    append_highlighted(&mut job, &result.name, result.name_range.clone(), body, highlight);
+    if !result.path.is_empty() {
+        job.append(&format!("\n{}", result.path), 0., small.clone());
+    }
+    if let Some(detail) = &result.detail {
+        job.append(&format!("\n{}: ", detail.field), 0., small.clone());
+        append_highlighted(&mut job, &detail.text, Some(detail.range.clone()), small, highlight);
+    }
+    job
+}
+
+fn append_highlighted(job: &mut LayoutJob, text: &str, range: Option<std::ops::Range<usize>>, format: TextFormat, highlight: Color32) {
+    let Some(range) = range else {
+        job.append(text, 0., format);
+        return;
+    };
+    job.append(&text[..range.start], 0., format.clone());
+    job.append(&text[range.clone()], 0., TextFormat { background: highlight, ..format.clone() });
+    job.append(&text[range.end..], 0., format);
+}
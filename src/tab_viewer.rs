@@ -2,14 +2,24 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::{collections::HashMap, rc::Rc, cell::RefCell};
+use std::{collections::{HashMap, VecDeque}, rc::Rc, cell::RefCell};
 
 use egui::Ui;
 use serde::{Serialize, Deserialize};
 
 use uuid::Uuid;
 
-use crate::{collection::Collection, request::Request};
+use crate::{collection::Collection, request::{Request, RequestData}};
+
+/// The storage key of the drafts. They are kept apart from the app state, so a broken draft can't lose the collections
+pub const DRAFTS_KEY: &str = "drafts";
+
+/// The unsaved changes of an open request
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Draft {
+    pub uuid: Uuid,
+    pub request_data: RequestData,
+}
 
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Default)]
@@ -17,6 +27,12 @@ pub struct TabViewer {
     pub collections: Rc<RefCell<Vec<Collection>>>,
     
     pub requests: HashMap<Uuid, Request>,
+    /// Scratch requests that want to be saved. The app asks which collection each one should go to
+    #[serde(skip)]
+    pub save_to_collection: VecDeque<Uuid>,
+    /// A request tab with unsaved changes that the user wants to close. The app asks what to do with them
+    #[serde(skip)]
+    pub confirm_close: Option<Uuid>,
 }
 
 impl TabViewer {
@@ -26,6 +42,51 @@ impl TabViewer {
             ..Default::default()
         }
     }
+    
+    pub fn drafts(&self) -> Vec<Draft> {
+        self.requests.values()
+            .filter(|r| r.changed_since_save())
+            .map(|r| Draft { uuid: r.uuid, request_data: r.request_data().clone() })
+            .collect()
+    }
+    
+    /// The open requests with unsaved changes
+    pub fn unsaved(&self) -> Vec<Uuid> {
+        self.requests.values().filter(|r| r.changed_since_save()).map(|r| r.uuid).collect()
+    }
+    
+    /// Saves the request into its collection. Scratch requests ask where they should go instead and return false
+    pub fn save(&mut self, uuid: &Uuid) -> bool {
+        let Some(request) = self.requests.get_mut(uuid) else {
+            return true;
+        };
+        let mut collections = self.collections.borrow_mut();
+        if collections.iter().all(|c| c.items.find_request(uuid).is_none()) {
+            ask_for_collection(&mut self.save_to_collection, *uuid);
+            return false;
+        }
+        request.wants_save = true;
+        request.do_save();
+        save_request(&mut collections, request)
+    }
+}
+
+/// Replaces the saved request in its collection with the open one. Returns false if it isn't in any collection
+fn save_request(collections: &mut [Collection], request: &Request) -> bool {
+    let mut saved = false;
+    for collection in collections.iter_mut() {
+        if let Some(collection_request) = collection.items.find_request_mut(&request.uuid) {
+            *collection_request = request.clone();
+            saved = true;
+        }
+    }
+    saved
+}
+
+fn ask_for_collection(queue: &mut VecDeque<Uuid>, uuid: Uuid) {
+    if !queue.contains(&uuid) {
+        queue.push_back(uuid);
+    }
 }
 
 impl egui_dock::TabViewer for TabViewer {
@@ -33,27 +94,26 @@ impl egui_dock::TabViewer for TabViewer {
     
     fn ui(&mut self, ui: &mut Ui, tab: &mut Self::Tab) {
         let mut collections = self.collections.borrow_mut();
-        let collection = collections.iter_mut().find(|c| &c.uuid == tab);
         let request = self.requests.get_mut(tab);
        
-        if let Some(collection) = collection {
+        if let Some(collection) = collections.iter_mut().find(|c| &c.uuid == tab) {
             collection.render(ui);
+        } else if let Some(folder) = collections.iter_mut().find_map(|c| c.items.find_folder_mut(tab)) {
+            folder.render(ui);
         }
        
         if let Some(request) = request {
-            if request.do_save() {
-                let mut saved = false;
-                for collection in collections.iter_mut() {
-                    println!("Requests: {:?}", collection.requests);
-                    if let Some(collection_request) = collection.requests.iter_mut().find(|cr| cr.uuid == request.uuid) {
-                        *collection_request = request.clone();
-                        saved = true;
-                    }
-                };
-                if !saved {
-                    panic!("PANIC: WE DID NOT SAVE REQUEST UUID: {}", request.uuid);
-                }
-                
+            // The request might have been moved to another folder since the tab was opened
+            let scope = collections.iter().find_map(|c| c.scope_of(tab));
+            let is_scratch = scope.is_none();
+            if let Some((collection_data, folder_data)) = scope {
+                request.set_scope(collection_data, folder_data);
+            }
+            if is_scratch && std::mem::take(&mut request.wants_save) {
+                ask_for_collection(&mut self.save_to_collection, request.uuid);
+            } else if request.do_save() && !save_request(&mut collections, request) {
+                // It was removed from its collection in the meantime
+                ask_for_collection(&mut self.save_to_collection, request.uuid);
             }
             request.render(ui);
         }
@@ -62,10 +122,12 @@ impl egui_dock::TabViewer for TabViewer {
     fn title(&mut self, tab: &mut Self::Tab) -> egui::WidgetText {
         let collections = self.collections.borrow_mut();
         let collection = collections.iter().find(|c| &c.uuid == tab);
+        let folder = collections.iter().find_map(|c| c.items.find_folder(tab));
         let request = self.requests.get(tab);
         
         let Some(name) = 
              collection.map(|c| c.name.clone())
+             .or(folder.map(|f| f.name.clone()))
              .or(request.map(|r| {
                     match r.changed_since_save() {
                         true => format!("*{}", r.name()),
@@ -84,7 +146,31 @@ impl egui_dock::TabViewer for TabViewer {
     }
 
     fn on_close(&mut self, tab: &mut Self::Tab) -> bool {
+        if self.requests.get(tab).map_or(false, Request::changed_since_save) {
+            self.confirm_close = Some(*tab);
+            return false;
+        }
         self.requests.remove(tab);
         true
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scratch_requests_wait_for_a_collection_one_after_the_other() {
+        let mut tab_viewer = TabViewer::default();
+        let first = Request::scratch();
+        let second = Request::scratch();
+        let uuids = [first.uuid, second.uuid];
+        tab_viewer.requests.insert(first.uuid, first);
+        tab_viewer.requests.insert(second.uuid, second);
+
+        for uuid in uuids.iter().chain(&uuids) {
+            assert!(!tab_viewer.save(uuid));
+        }
+        assert_eq!(tab_viewer.save_to_collection, VecDeque::from(uuids));
+    }
+}
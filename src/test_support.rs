@@ -0,0 +1,55 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Helpers shared by the tests of several modules
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+
+/// A local HTTP stand-in that answers each request with what `answer` returns for it.
+/// Returns its address and the raw requests it got
+pub(crate) fn stand_in(mut answer: impl FnMut(&str) -> Vec<u8> + Send + 'static) -> (String, Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let (sender, requests) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let request = read_request(&mut stream);
+            let _ = stream.write_all(&answer(&request));
+            // Not every test looks at the requests
+            let _ = sender.send(request);
+        }
+    });
+    (address, requests)
+}
+
+/// An empty response with the status
+pub(crate) fn status(status: u16) -> Vec<u8> {
+    format!("HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).into_bytes()
+}
+
+/// Reads the head of a request and as much of its body as the `Content-Length` says
+fn read_request(stream: &mut TcpStream) -> String {
+    let mut received = vec![];
+    let mut buffer = [0; 1024];
+    loop {
+        // The peer may close the connection before the request is complete
+        let read = stream.read(&mut buffer).unwrap_or(0);
+        if read == 0 {
+            break;
+        }
+        received.extend_from_slice(&buffer[..read]);
+        if let Some(end) = received.windows(4).position(|w| w == b"\r\n\r\n") {
+            let length = String::from_utf8_lossy(&received[..end]).lines()
+                .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse().unwrap()))
+                .unwrap_or(0);
+            if received.len() >= end + 4 + length {
+                break;
+            }
+        }
+    }
+    String::from_utf8_lossy(&received).into_owned()
+}
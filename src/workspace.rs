@@ -0,0 +1,505 @@
+// SPDX-FileCopyrightText: 2023 Frieder Hannenheim <frieder.hannenheim@pm.me>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! A directory of collections that can be committed to a repository.
+//!
+//! ```text
+//! workspace.json                          the collection directories in order
+//! users-api/collection.json               name, auth, variables, ... and the folders and requests in order
+//! users-api/requests/*.json               one file per request
+//! users-api/requests/admin/folder.json    a folder, with its requests and folders next to it
+//! ```
+//!
+//! Only the data is written, no UUIDs or UI state, so the files only change when the requests do.
+//!
+//! Basic auth passwords, bearer tokens and JWT keys are not written into these files. They go into
+//! `secrets.local.json` next to `workspace.json`, which is added to the `.gitignore` of the workspace
+//! and read again on load. Secrets that are `{{variable}}` references are written as they are.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+
+use crate::collection::{CollectionData, CollectionFile, FolderData, FolderFile};
+use crate::request::RequestData;
+use crate::tabs::auth::{AuthData, AuthType};
+
+const WORKSPACE_FILE: &str = "workspace.json";
+const SECRETS_FILE: &str = "secrets.local.json";
+const GITIGNORE_FILE: &str = ".gitignore";
+const COLLECTION_FILE: &str = "collection.json";
+const FOLDER_FILE: &str = "folder.json";
+const REQUESTS_DIR: &str = "requests";
+
+#[derive(Serialize, Deserialize, Default)]
+struct WorkspaceIndex {
+    /// Directory names of the collections
+    collections: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CollectionIndex {
+    name: String,
+    data: CollectionData,
+    /// Directory names of the folders in the requests directory
+    #[serde(default)]
+    folders: Vec<String>,
+    /// File names of the requests in the requests directory
+    requests: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FolderIndex {
+    name: String,
+    data: FolderData,
+    #[serde(default)]
+    folders: Vec<String>,
+    #[serde(default)]
+    requests: Vec<String>,
+}
+
+/// The secrets of a workspace, by the file they were taken out of and their field,
+/// e.g. `users-api/requests/login.json#password`
+struct Secrets<'a> {
+    root: &'a Path,
+    secrets: BTreeMap<String, String>,
+}
+
+impl<'a> Secrets<'a> {
+    fn load(root: &'a Path) -> anyhow::Result<Self> {
+        let secrets = match root.join(SECRETS_FILE).is_file() {
+            true => read_json(&root.join(SECRETS_FILE))?,
+            false => BTreeMap::new(),
+        };
+        Ok(Self { root, secrets })
+    }
+
+    /// Writes the secrets and makes sure git ignores them. Removes the file if there are none
+    fn save(&self) -> anyhow::Result<()> {
+        let path = self.root.join(SECRETS_FILE);
+        if self.secrets.is_empty() {
+            return match path.exists() {
+                true => remove_file(&path),
+                false => Ok(()),
+            };
+        }
+        write_json(&path, &self.secrets)?;
+
+        let gitignore = self.root.join(GITIGNORE_FILE);
+        let mut ignored = std::fs::read_to_string(&gitignore).unwrap_or_default();
+        if !ignored.lines().any(|line| line.trim() == SECRETS_FILE) {
+            if !ignored.is_empty() && !ignored.ends_with('\n') {
+                ignored.push('\n');
+            }
+            ignored.push_str(SECRETS_FILE);
+            ignored.push('\n');
+            std::fs::write(&gitignore, ignored).with_context(|| format!("Could not write {}", gitignore.display()))?;
+        }
+        Ok(())
+    }
+
+    fn key(&self, file: &Path, field: &str) -> String {
+        let file = file.strip_prefix(self.root).unwrap_or(file);
+        let file: Vec<_> = file.components().map(|c| c.as_os_str().to_string_lossy()).collect();
+        format!("{}#{}", file.join("/"), field)
+    }
+
+    /// Empties the secrets in the auth data of the file and keeps them
+    fn take(&mut self, file: &Path, auth: &mut BTreeMap<AuthType, AuthData>) {
+        for data in auth.values_mut() {
+            if let Some((field, secret)) = secret_mut(data) {
+                if !secret.is_empty() && !is_reference(secret) {
+                    self.secrets.insert(self.key(file, field), std::mem::take(secret));
+                }
+            }
+        }
+    }
+
+    /// Puts the secrets back into the auth data of the file
+    fn restore(&self, file: &Path, auth: &mut BTreeMap<AuthType, AuthData>) {
+        for data in auth.values_mut() {
+            if let Some((field, secret)) = secret_mut(data) {
+                if let Some(value) = self.secrets.get(&self.key(file, field)) {
+                    *secret = value.clone();
+                }
+            }
+        }
+    }
+}
+
+/// The name and the value of the field that holds the secret
+fn secret_mut(auth: &mut AuthData) -> Option<(&'static str, &mut String)> {
+    match auth {
+        AuthData::Basic { password, .. } => Some(("password", password)),
+        AuthData::Bearer { token } => Some(("token", token)),
+        AuthData::Jwt { key, .. } => Some(("key", key)),
+        _ => None,
+    }
+}
+
+/// True for `{{name}}`, which is filled in from the variables when sending
+fn is_reference(value: &str) -> bool {
+    let value = value.trim();
+    value.strip_prefix("{{").and_then(|v| v.strip_suffix("}}")).map_or(false, |name| !name.contains(['{', '}']))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Workspace {
+    pub collections: Vec<CollectionFile>,
+}
+
+impl Workspace {
+    /// Loads every collection of the workspace. Collections, folders and requests that were added by
+    /// hand and are missing from the index files are loaded too, sorted by their file names
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let index: WorkspaceIndex = match path.join(WORKSPACE_FILE).exists() {
+            true => read_json(&path.join(WORKSPACE_FILE))?,
+            false => Default::default(),
+        };
+        let directories = with_unlisted(index.collections, list_entries(path, |p| p.join(COLLECTION_FILE).is_file())?);
+        let secrets = Secrets::load(path)?;
+
+        let mut collections = vec![];
+        for directory in directories {
+            let directory = path.join(directory);
+            let mut index: CollectionIndex = read_json(&directory.join(COLLECTION_FILE))?;
+            secrets.restore(&directory.join(COLLECTION_FILE), &mut index.data.auth);
+            let (folders, requests) = load_items(&directory.join(REQUESTS_DIR), index.folders, index.requests, &secrets)?;
+            collections.push(CollectionFile { name: index.name, data: index.data, folders, requests });
+        }
+        Ok(Self { collections })
+    }
+
+    /// Writes the workspace and removes the files of collections, folders and requests that no longer exist
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::create_dir_all(path).with_context(|| format!("Could not create {}", path.display()))?;
+        let old_directories = list_entries(path, |p| p.join(COLLECTION_FILE).is_file())?;
+        let mut secrets = Secrets { root: path, secrets: BTreeMap::new() };
+
+        let mut directories = vec![];
+        for collection in &self.collections {
+            let directory = unique_name(&slug(&collection.name, "collection"), "", &directories);
+            let requests_dir = path.join(&directory).join(REQUESTS_DIR);
+            let (folders, requests) = save_items(&requests_dir, &collection.folders, &collection.requests, &mut secrets)?;
+
+            let file = path.join(&directory).join(COLLECTION_FILE);
+            let mut index = CollectionIndex { name: collection.name.clone(), data: collection.data.clone(), folders, requests };
+            secrets.take(&file, &mut index.data.auth);
+            write_json(&file, &index)?;
+            directories.push(directory);
+        }
+        secrets.save()?;
+
+        for stale in old_directories.iter().filter(|d| !directories.contains(d)) {
+            let directory = path.join(stale);
+            remove_items(&directory.join(REQUESTS_DIR))?;
+            remove_file(&directory.join(COLLECTION_FILE))?;
+            let _ = std::fs::remove_dir(directory);
+        }
+
+        write_json(&path.join(WORKSPACE_FILE), &WorkspaceIndex { collections: directories })
+    }
+}
+
+fn is_request_file(path: &Path) -> bool {
+    path.extension().map_or(false, |e| e == "json") && path.file_name().map_or(false, |n| n != FOLDER_FILE)
+}
+
+fn is_folder(path: &Path) -> bool {
+    path.join(FOLDER_FILE).is_file()
+}
+
+/// The listed names followed by the ones that exist but are not listed
+fn with_unlisted(mut listed: Vec<String>, existing: Vec<String>) -> Vec<String> {
+    for name in existing {
+        if !listed.contains(&name) {
+            listed.push(name);
+        }
+    }
+    listed
+}
+
+fn load_items(directory: &Path, folders: Vec<String>, requests: Vec<String>, secrets: &Secrets<'_>) -> anyhow::Result<(Vec<FolderFile>, Vec<RequestData>)> {
+    let (folders, requests) = match directory.is_dir() {
+        true => (
+            with_unlisted(folders, list_entries(directory, is_folder)?),
+            with_unlisted(requests, list_entries(directory, is_request_file)?),
+        ),
+        false => (folders, requests),
+    };
+
+    let requests = requests.iter()
+        .map(|file| {
+            let file = directory.join(file);
+            let mut request: RequestData = read_json(&file)?;
+            secrets.restore(&file, &mut request.auth);
+            Ok(request)
+        })
+        .collect::<anyhow::Result<_>>()?;
+    let folders = folders.iter()
+        .map(|name| {
+            let directory = directory.join(name);
+            let mut index: FolderIndex = read_json(&directory.join(FOLDER_FILE))?;
+            secrets.restore(&directory.join(FOLDER_FILE), &mut index.data.auth);
+            let (folders, requests) = load_items(&directory, index.folders, index.requests, secrets)?;
+            Ok(FolderFile { name: index.name, data: index.data, folders, requests })
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok((folders, requests))
+}
+
+/// Writes the folders and requests into the directory and removes the ones that no longer exist.
+/// Returns the names of the folder directories and request files in order
+fn save_items(directory: &Path, folders: &[FolderFile], requests: &[RequestData], secrets: &mut Secrets<'_>) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+    std::fs::create_dir_all(directory).with_context(|| format!("Could not create {}", directory.display()))?;
+
+    let mut files = vec![String::from(FOLDER_FILE)];
+    for request in requests {
+        let file = unique_name(&slug(&request.name, "request"), ".json", &files);
+        let mut request = request.clone();
+        secrets.take(&directory.join(&file), &mut request.auth);
+        write_json(&directory.join(&file), &request)?;
+        files.push(file);
+    }
+    files.remove(0);
+
+    let mut directories = vec![];
+    for folder in folders {
+        let name = unique_name(&slug(&folder.name, "folder"), "", &directories);
+        let (sub_folders, sub_requests) = save_items(&directory.join(&name), &folder.folders, &folder.requests, secrets)?;
+        let file = directory.join(&name).join(FOLDER_FILE);
+        let mut index = FolderIndex { name: folder.name.clone(), data: folder.data.clone(), folders: sub_folders, requests: sub_requests };
+        secrets.take(&file, &mut index.data.auth);
+        write_json(&file, &index)?;
+        directories.push(name);
+    }
+
+    for stale in list_entries(directory, is_request_file)?.iter().filter(|f| !files.contains(f)) {
+        remove_file(&directory.join(stale))?;
+    }
+    for stale in list_entries(directory, is_folder)?.iter().filter(|d| !directories.contains(d)) {
+        remove_items(&directory.join(stale))?;
+    }
+    Ok((directories, files))
+}
+
+/// Turns a name into something that works as a file name everywhere
+fn slug(name: &str, fallback: &str) -> String {
+    let mut slug = String::new();
+    for c in name.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    match slug.is_empty() {
+        true => fallback.to_string(),
+        false => slug.to_string(),
+    }
+}
+
+/// Appends a number if two collections or requests end up with the same file name
+fn unique_name(slug: &str, extension: &str, taken: &[String]) -> String {
+    let mut name = format!("{}{}", slug, extension);
+    let mut i = 2;
+    while taken.contains(&name) {
+        name = format!("{}-{}{}", slug, i, extension);
+        i += 1;
+    }
+    name
+}
+
+/// The names of the entries in a directory that match the filter, sorted
+fn list_entries(path: &Path, filter: impl Fn(&Path) -> bool) -> anyhow::Result<Vec<String>> {
+    let entries = std::fs::read_dir(path).with_context(|| format!("Could not read {}", path.display()))?;
+    let mut names = BTreeSet::new();
+    for entry in entries {
+        let entry_path: PathBuf = entry?.path();
+        if filter(&entry_path) {
+            if let Some(name) = entry_path.file_name().and_then(|n| n.to_str()) {
+                names.insert(name.to_string());
+            }
+        }
+    }
+    Ok(names.into_iter().collect())
+}
+
+/// Removes the folders and requests in the directory. Only removes the files a workspace consists of,
+/// so nothing else in the directory gets lost
+fn remove_items(directory: &Path) -> anyhow::Result<()> {
+    if !directory.is_dir() {
+        return Ok(());
+    }
+    for folder in list_entries(directory, is_folder)? {
+        remove_items(&directory.join(folder))?;
+    }
+    for file in list_entries(directory, |p| p.extension().map_or(false, |e| e == "json"))? {
+        remove_file(&directory.join(file))?;
+    }
+    let _ = std::fs::remove_dir(directory);
+    Ok(())
+}
+
+fn remove_file(path: &Path) -> anyhow::Result<()> {
+    std::fs::remove_file(path).with_context(|| format!("Could not remove {}", path.display()))
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let file = std::fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+    serde_json::from_str(&file).with_context(|| format!("{} is not valid", path.display()))
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> anyhow::Result<()> {
+    let mut file = serde_json::to_string_pretty(value)?;
+    file.push('\n');
+    std::fs::write(path, file).with_context(|| format!("Could not write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let path = std::env::temp_dir().join(format!("packets-workspace-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    fn collection(name: &str, requests: &[&str]) -> CollectionFile {
+        CollectionFile {
+            name: name.to_string(),
+            data: CollectionData::default(),
+            folders: vec![],
+            requests: requests.iter().map(|name| RequestData { name: name.to_string(), ..Default::default() }).collect(),
+        }
+    }
+
+    #[test]
+    fn round_trip_keeps_order_and_names() {
+        let path = temp_dir();
+        let mut workspace = Workspace {
+            collections: vec![collection("Users API", &["List users", "Get user", "Get user"]), collection("Billing", &[])],
+        };
+        workspace.collections[0].data.variables.push((String::from("host"), String::from("localhost")));
+        workspace.save(&path).unwrap();
+
+        assert!(path.join("users-api/requests/get-user-2.json").is_file());
+        let loaded = Workspace::load(&path).unwrap();
+        assert_eq!(loaded.collections.len(), 2);
+        assert_eq!(loaded.collections[0].name, "Users API");
+        assert_eq!(loaded.collections[0].data, workspace.collections[0].data);
+        assert_eq!(loaded.collections[0].requests, workspace.collections[0].requests);
+        assert_eq!(loaded.collections[1].name, "Billing");
+
+        // Saving again without changes writes the same bytes
+        let before = std::fs::read(path.join("users-api/collection.json")).unwrap();
+        loaded.save(&path).unwrap();
+        assert_eq!(std::fs::read(path.join("users-api/collection.json")).unwrap(), before);
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn folders_are_directories() {
+        let path = temp_dir();
+        let mut workspace = Workspace { collections: vec![collection("API", &["Health"])] };
+        let inner = FolderFile {
+            name: String::from("Admin"),
+            requests: vec![RequestData { name: String::from("Folder"), ..Default::default() }],
+            ..Default::default()
+        };
+        workspace.collections[0].folders.push(FolderFile {
+            name: String::from("Users"),
+            data: FolderData { variables: vec![(String::from("path"), String::from("/users"))], ..Default::default() },
+            folders: vec![inner],
+            requests: vec![RequestData { name: String::from("List"), ..Default::default() }],
+        });
+        workspace.save(&path).unwrap();
+
+        assert!(path.join("api/requests/users/folder.json").is_file());
+        assert!(path.join("api/requests/users/admin/folder-2.json").is_file());
+        let loaded = Workspace::load(&path).unwrap();
+        let users = &loaded.collections[0].folders[0];
+        assert_eq!(users.data, workspace.collections[0].folders[0].data);
+        assert_eq!(users.requests[0].name, "List");
+        assert_eq!(users.folders[0].requests[0].name, "Folder");
+
+        workspace.collections[0].folders.clear();
+        workspace.save(&path).unwrap();
+        assert!(!path.join("api/requests/users").exists());
+        assert!(path.join("api/requests/health.json").is_file());
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn secrets_stay_out_of_the_committed_files() {
+        let path = temp_dir();
+        std::fs::write(path.join(".gitignore"), "target").unwrap();
+        let mut workspace = Workspace { collections: vec![collection("API", &["Login", "Me"])] };
+        let api = &mut workspace.collections[0];
+        api.data.auth.insert(AuthType::Basic, AuthData::Basic { username: String::from("ada"), password: String::from("hunter2") });
+        api.requests[1].auth.insert(AuthType::Bearer, AuthData::Bearer { token: String::from("{{token}}") });
+        api.folders.push(FolderFile {
+            name: String::from("Admin"),
+            data: FolderData {
+                auth: BTreeMap::from([(AuthType::Jwt, AuthData::Jwt {
+                    algorithm: Default::default(),
+                    key: String::from("signing secret"),
+                    claims: String::new(),
+                    expires_in: 60,
+                })]),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        workspace.save(&path).unwrap();
+
+        let committed = std::fs::read_to_string(path.join("api/collection.json")).unwrap();
+        assert!(committed.contains("ada") && !committed.contains("hunter2"));
+        assert!(!std::fs::read_to_string(path.join("api/requests/admin/folder.json")).unwrap().contains("signing secret"));
+        // References are committed as they are
+        assert!(std::fs::read_to_string(path.join("api/requests/me.json")).unwrap().contains("{{token}}"));
+        let secrets: BTreeMap<String, String> = read_json(&path.join(SECRETS_FILE)).unwrap();
+        assert_eq!(secrets, BTreeMap::from([
+            (String::from("api/collection.json#password"), String::from("hunter2")),
+            (String::from("api/requests/admin/folder.json#key"), String::from("signing secret")),
+        ]));
+        assert_eq!(std::fs::read_to_string(path.join(".gitignore")).unwrap(), "target\nsecrets.local.json\n");
+
+        let loaded = Workspace::load(&path).unwrap();
+        assert_eq!(loaded.collections[0].data, workspace.collections[0].data);
+        assert_eq!(loaded.collections[0].folders[0].data, workspace.collections[0].folders[0].data);
+        assert_eq!(loaded.collections[0].requests, workspace.collections[0].requests);
+
+        // Saving twice doesn't list the file twice, and it goes away with the last secret
+        loaded.save(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(path.join(".gitignore")).unwrap(), "target\nsecrets.local.json\n");
+        Workspace { collections: vec![collection("API", &[])] }.save(&path).unwrap();
+        assert!(!path.join(SECRETS_FILE).exists());
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn removed_requests_and_collections_are_deleted() {
+        let path = temp_dir();
+        Workspace { collections: vec![collection("A", &["One", "Two"]), collection("B", &["Three"])] }.save(&path).unwrap();
+        std::fs::write(path.join("README.md"), "not ours").unwrap();
+
+        Workspace { collections: vec![collection("A", &["One"])] }.save(&path).unwrap();
+
+        assert!(path.join("a/requests/one.json").is_file());
+        assert!(!path.join("a/requests/two.json").exists());
+        assert!(!path.join("b").exists());
+        assert!(path.join("README.md").is_file());
+        assert_eq!(Workspace::load(&path).unwrap().collections.len(), 1);
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}